@@ -1,123 +1,378 @@
-use std::collections::HashMap;
-use std::env;
-use std::fs::File;
-use std::io::{prelude::*, BufReader};
-use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
 
-use chrono::{DateTime, Duration, FixedOffset, Utc};
+use chrono::{DateTime, Duration, Utc};
 
-use crate::mongo_api;
-use crate::parse_timeanddate_dot_com::{download_time_data, Sort, TimeData};
+#[cfg(feature = "http-fetch")]
+use crate::cache_store::Result;
+use crate::cache_store::{CacheStore, FileStore, MemoryStore};
+use crate::config::{CacheConfig, Config, HistoryKind, StoreKind};
+#[cfg(feature = "http-fetch")]
+use crate::history::Retention;
+use crate::history::{FileHistory, HistoryStore};
+#[cfg(feature = "mongo")]
+use crate::mongo_api::{CacheWatcher, MongoStore};
+#[cfg(feature = "http-fetch")]
+use crate::parse_timeanddate_dot_com::try_download_time_data;
+use crate::parse_timeanddate_dot_com::{PageOptions, TimeData};
+#[cfg(feature = "http-fetch")]
+use crate::snapshot_diff;
+#[cfg(feature = "sqlite")]
+use crate::sqlite_store::SqliteStore;
+use crate::time_conversion;
+use crate::url_list;
+use crate::url_list::UrlEntry;
 
-/// This Cache-feature assumes a LOCAL MONGODB.
-/// Storing City Data in MongoDb but only if the 'cache' program argument has been set.
-/// Also an optional ttl=480 can be provided: this sets the Time To Live of the stored (cached) data  in [minutes]
-pub fn fetch_time_data() -> HashMap<String, TimeData> {
+/// Downloads the time-data of the configured urls, or serves it from the cache when cache.enabled is set.
+/// The settings - cache.ttl_minutes, cache.store, cache.max_stale_minutes and the like - come from the
+/// configuration-file, the environment and the command-line; see [crate::config::Config].
+/// A page in 'urls.txt' can have its own Time To Live; e.g. 'Europa=https://... ttl=120'
+/// When refreshing fails, expired time-data is served for at most cache.max_stale_minutes; it is marked 'stale'.
+/// Fails when a page can be neither downloaded nor served from the cache.
+#[cfg(feature = "http-fetch")]
+pub fn fetch_time_data(config: &Config) -> Result<HashMap<String, TimeData>> {
     //The Urls to download the time-data from...
-    let urls = &urls_from("urls.txt");
+    let urls = &config.urls();
 
-    //Is the time-data to be stored in a MongoDb?...
-    if do_cache() {
-        return use_cache(urls);
+    //Is the time-data to be cached?...
+    if config.cache.enabled {
+        return use_cache(
+            config,
+            cache_store(&config.cache).as_ref(),
+            urls,
+            &config.page_ttls(),
+        );
     }
-    download_time_data(Sort::ByName, urls)
+    try_download_time_data(config.fetch_options(), urls)
+        .into_iter()
+        .map(|(key, page)| page.map(|td| (key, td)))
+        .collect()
 }
 
-fn use_cache(urls: &HashMap<String, String>) -> HashMap<String, TimeData> {
-    let mut map = mongo_api::load_stored_time_data();
+///Pages without an entry in 'page_ttls' use the global Time To Live; see [fetch_time_data].
+/// Fails when the store fails, or when nothing could be downloaded and there is no usable cached time-data.
+#[cfg(feature = "http-fetch")]
+pub fn use_cache(
+    config: &Config,
+    store: &dyn CacheStore,
+    urls: &HashMap<String, String>,
+    page_ttls: &HashMap<String, Duration>,
+) -> Result<HashMap<String, TimeData>> {
+    let map = store
+        .load_all()
+        .map_err(|e| format!("Error Occurred whilst reading all time-data: {}", e))?;
 
-    // Is there cached data, does it have the same document count as the number of requested web-pages
-    // or has it expired?...
-    if cache_invalid(&map, &urls) {
-        println!("Refreshing Cache: downloading all time-data now...");
-        map = download_time_data(Sort::ByName, urls);
-        mongo_api::replace_stored_data_with(&map);
-    } else {
+    //Which pages are missing, have expired or are no longer wanted?...
+    let validity = page_validity(&map, urls, page_ttls, config.cache.ttl());
+    let to_fetch: HashMap<String, String> = urls
+        .iter()
+        .filter(|(key, _)| validity[*key] != PageValidity::Fresh)
+        .map(|(key, url)| (key.to_string(), url.to_string()))
+        .collect();
+    let to_prune: Vec<&String> = validity
+        .iter()
+        .filter(|(_, v)| **v == PageValidity::Unwanted)
+        .map(|(key, _)| key)
+        .collect();
+
+    if to_fetch.is_empty() && to_prune.is_empty() {
         println!("Serving up time-data from cache...");
+        return Ok(map);
+    }
+
+    println!(
+        "Refreshing Cache: downloading {} of {} pages now; pruning {} pages...",
+        to_fetch.len(),
+        urls.len(),
+        to_prune.len()
+    );
+    let mut fresh = HashMap::new();
+    let mut stale = HashMap::new();
+    let max_staleness = config.cache.max_staleness();
+
+    //A page that could not be downloaded is served from the cache - stale-if-error - unless it is too old...
+    for (key, downloaded) in try_download_time_data(config.fetch_options(), &to_fetch) {
+        match (downloaded, map.get(&key)) {
+            (Ok(td), _) => {
+                fresh.insert(key, td);
+            }
+            (Err(e), Some(cached)) if cached.age() <= max_staleness => {
+                println!(
+                    "Could not refresh {}: {}. Serving STALE time-data instead; it is {} old.",
+                    key,
+                    e,
+                    cached.age()
+                );
+                stale.insert(
+                    key,
+                    TimeData {
+                        stale: true,
+                        ..cached.clone()
+                    },
+                );
+            }
+            (Err(e), _) => println!(
+                "Could not refresh {}: {}. There is no usable cached time-data (maximum staleness = {}).",
+                key, e, max_staleness
+            ),
+        }
+    }
+    if fresh.is_empty() && stale.is_empty() && validity.values().all(|v| *v != PageValidity::Fresh)
+    {
+        return Err(
+            "Could not download any time-data and there is no usable cached time-data".into(),
+        );
+    }
+
+    //Share what changed since the previous refresh; e.g. DST-switches. Only the pages that were downloaded
+    // again count: a page that failed - or was pruned - did not lose its cities...
+    let refreshed: HashMap<String, TimeData> = map
+        .iter()
+        .filter(|(key, _)| fresh.contains_key(*key))
+        .map(|(key, td)| (key.to_string(), td.clone()))
+        .collect();
+    if !refreshed.is_empty() {
+        println!("{}", snapshot_diff::diff(&refreshed, &fresh));
+    }
+
+    //Nothing worth keeping: replace it all in one go. Otherwise leave the fresh - and stale - pages untouched...
+    if stale.is_empty() && validity.values().all(|v| *v != PageValidity::Fresh) {
+        store
+            .replace_all(&fresh)
+            .map_err(|e| format!("Error Occurred whilst storing all time-data: {}", e))?;
+    } else {
+        store
+            .put_many(&fresh)
+            .map_err(|e| format!("Error Occurred whilst storing the time-data: {}", e))?;
+        for key in to_prune {
+            store
+                .remove(key)
+                .map_err(|e| format!("Error Occurred whilst pruning time-data {}: {}", key, e))?;
+        }
+    }
+    if config.history.enabled && !fresh.is_empty() {
+        record_history(
+            history_store(config).as_ref(),
+            &config.history.retention(),
+            &fresh,
+        );
+    }
+    let mut map = store
+        .load_all()
+        .map_err(|e| format!("Error Occurred whilst reading all time-data: {}", e))?;
+    map.extend(stale);
+    Ok(map)
+}
+
+///Appends the freshly downloaded pages to the history and prunes what the retention-policy no longer allows.
+/// The history is a nice-to-have: failing to keep it never fails the refresh.
+#[cfg(feature = "http-fetch")]
+pub(crate) fn record_history(
+    history: &dyn HistoryStore,
+    retention: &Retention,
+    fresh: &HashMap<String, TimeData>,
+) {
+    if let Err(e) = history.append(Utc::now(), fresh) {
+        println!("Could not append to the history: {}", e);
+        return;
+    }
+    match history.prune(retention) {
+        Ok(0) => {}
+        Ok(pruned) => println!("Pruned {} snapshots from the history...", pruned),
+        Err(e) => println!("Could not prune the history: {}", e),
+    }
+}
+
+///How a page is to be treated when the cache is refreshed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageValidity {
+    ///Cached and within its Time To Live: left untouched.
+    Fresh,
+    ///Cached but past its Time To Live - or a DST-transition happened since - : downloaded again.
+    Expired,
+    ///In the urls but not cached (yet): downloaded.
+    Missing,
+    ///Cached but no longer in the urls: pruned.
+    Unwanted,
+}
+
+///The verdict for every page that is either cached, in the urls, or both.
+/// A cached page also expires early when any of its cities went through a DST-transition since it was stored:
+/// its offsets are then definitely wrong.
+pub fn page_validity(
+    cached_data: &HashMap<String, TimeData>,
+    urls: &HashMap<String, String>,
+    page_ttls: &HashMap<String, Duration>,
+    default_ttl: Duration,
+) -> BTreeMap<String, PageValidity> {
+    let now = Utc::now();
+    let ttl_of = |key: &str| *page_ttls.get(key).unwrap_or(&default_ttl);
+
+    let validity: BTreeMap<String, PageValidity> = urls
+        .keys()
+        .chain(cached_data.keys())
+        .map(|key| {
+            let v = match (urls.contains_key(key), cached_data.get(key)) {
+                (false, _) => PageValidity::Unwanted,
+                (true, None) => PageValidity::Missing,
+                (true, Some(td)) if now - td.last_updated > ttl_of(key) => PageValidity::Expired,
+                (true, Some(td)) => match dst_transition_since(td, &now) {
+                    Some(city) => {
+                        println!(
+                            "Cached page {}: {} went through a DST-transition since {}.",
+                            key, city, td.last_updated
+                        );
+                        PageValidity::Expired
+                    }
+                    None => PageValidity::Fresh,
+                },
+            };
+            (key.to_string(), v)
+        })
+        .collect();
+
+    //Share the love...
+    for (key, v) in validity.iter() {
+        match cached_data.get(key) {
+            Some(td) => println!(
+                "Cached page {}: {:?}; age = {}; TTL (Time To Live) = {}.",
+                key,
+                v,
+                now - td.last_updated,
+                ttl_of(key)
+            ),
+            None => println!("Cached page {}: {:?}.", key, v),
+        }
     }
-    map
+    validity
+}
+
+///When the first of the cached pages expires by its Time To Live; DST-transitions are not foreseen.
+pub fn next_expiry(
+    cached_data: &HashMap<String, TimeData>,
+    page_ttls: &HashMap<String, Duration>,
+    default_ttl: Duration,
+) -> Option<DateTime<Utc>> {
+    cached_data
+        .iter()
+        .map(|(key, td)| td.last_updated + *page_ttls.get(key).unwrap_or(&default_ttl))
+        .min()
+}
+
+///The first city - if any - whose IANA-zone changed its UTC-offset between the page's 'last_updated' and now.
+fn dst_transition_since(td: &TimeData, now: &DateTime<Utc>) -> Option<String> {
+    td.city_times
+        .iter()
+        .find(|city| time_conversion::offset_changed_between(city, &td.last_updated, now))
+        .map(|city| city.name.to_string())
 }
 
 ///The cache is deemed invalid when:
 /// 1) There IS NO Cached-data
-/// 2) The number of cached items does NOT match the number of urls
-/// 3) NOT All urls-keys are present in the cache
+/// 2) ANY url-key is NOT present in the cache
+/// 3) ANY cached-item is no longer in the urls
 /// 4) ANY cached-item has expired.
-///So When there are six URLs and six cached items but the keys do not ALL match, this function will return false.
+///
+///So When there are six URLs and six cached items but the keys do not ALL match, this function will return true.
 pub fn cache_invalid(
     cached_data: &HashMap<String, TimeData>,
     urls: &HashMap<String, String>,
+    ttl: Duration,
 ) -> bool {
-    let last_updated = oldest_last_updated(cached_data);
-    let cache_time_to_live = ttl();
-
-    //The cache-validation criteria...
-    let cache_size_invalid = cached_data.len() == 0 || cached_data.len() != urls.len();
-    let cache_incomplete = urls
-        .iter()
-        .filter(|(key, _url)| !cached_data.contains_key(*key))
-        .count()
-        != 0;
-    let cache_expired = Utc::now() - last_updated > cache_time_to_live;
-
-    //Share the love...
-    println!(
-        "Cache age = {}; TTL (Time To Live) = {}; Cache expired: {}.  Cache contain correct number of elements: {}. All URLs have been cached: {}.",
-        (Utc::now() - last_updated), cache_time_to_live, cache_expired,
-        !cache_size_invalid, !cache_incomplete
-    );
-
-    //Return the verdict...
-    cache_size_invalid || cache_incomplete || cache_expired
+    cached_data.is_empty()
+        || page_validity(cached_data, urls, &HashMap::new(), ttl)
+            .values()
+            .any(|v| *v != PageValidity::Fresh)
 }
 
-///Commented-out urls  - line starts '//' or '#' - are ignored
+///Commented-out urls  - line starts '//' or '#' - and disabled urls are ignored; see [url_list::read].
 pub fn urls_from(file: &str) -> HashMap<String, String> {
-    let mut urls = HashMap::new();
-    for l in BufReader::new(File::open(file).expect(&format!("Please place file '{}' in the same directory as the executable; it contains the URLS to download the time-data from", file))).lines() {
-        let u = l.unwrap();
-        if can_use(&u) {
-            let d: Vec<&str> = u.splitn(2, "=").collect();
-            if d.len() == 2 && d[1].len() > 0 {
-                urls.insert(d[0].trim().to_string(), d[1].trim().to_string());
-            }
-        }
-    }
-    urls
+    url_entries_from(file)
+        .into_iter()
+        .map(|e| (e.key, e.url))
+        .collect()
 }
 
-fn can_use(u: &String) -> bool {
-    u.trim().len() != 0 && !u.trim().starts_with("#") && !u.trim().starts_with("//")
+///The per-page Time To Live - e.g. 'Europa=https://... ttl=120' [minutes] - of the urls that have one.
+pub fn page_ttls_from(file: &str) -> HashMap<String, Duration> {
+    url_entries_from(file)
+        .into_iter()
+        .filter_map(|e| e.ttl.map(|ttl| (e.key, ttl)))
+        .collect()
 }
 
-fn oldest_last_updated(map: &HashMap<String, TimeData>) -> DateTime<Utc> {
-    let mut oldest = Utc::now();
+///The parser and locale of every url; see [crate::parse_timeanddate_dot_com::FetchOptions].
+pub fn page_options_from(file: &str) -> HashMap<String, PageOptions> {
+    url_entries_from(file)
+        .into_iter()
+        .map(|e| {
+            let options = PageOptions {
+                parser: e.parser,
+                locale: e.locale,
+            };
+            (e.key, options)
+        })
+        .collect()
+}
 
-    //find oldest 'last_updated' date-time...
-    for td in map.values() {
-        let t = DateTime::<FixedOffset>::parse_from_rfc3339(&td.last_updated)
-            .unwrap()
-            .into();
-        if oldest > t {
-            oldest = t;
+fn url_entries_from(file: &str) -> Vec<UrlEntry> {
+    url_list::read(file)
+        .unwrap_or_else(|e| panic!("Please fix the URL list: {}", e))
+        .into_iter()
+        .filter(|e| e.enabled)
+        .collect()
+}
+
+///The configured cache-store; see [fetch_time_data].
+pub fn cache_store(cache: &CacheConfig) -> Box<dyn CacheStore> {
+    match cache.store {
+        StoreKind::File => Box::new(FileStore::new(&cache.file_path)),
+        #[cfg(feature = "sqlite")]
+        StoreKind::Sqlite => Box::new(SqliteStore::open(&cache.sqlite_path).unwrap_or_else(|e| {
+            panic!(
+                "Could not open the SQLite-cache '{}': {}",
+                cache.sqlite_path, e
+            )
+        })),
+        #[cfg(not(feature = "sqlite"))]
+        StoreKind::Sqlite => panic!("This build has no SQLite-support; see the 'sqlite'-feature"),
+        //One memory-store for the whole process; otherwise it would be empty every time...
+        StoreKind::Memory => {
+            static MEMORY_STORE: OnceLock<MemoryStore> = OnceLock::new();
+            Box::new(MEMORY_STORE.get_or_init(MemoryStore::new).clone())
         }
+        #[cfg(feature = "mongo")]
+        StoreKind::Mongo => Box::new(
+            MongoStore::configured(&cache.mongo)
+                .unwrap_or_else(|e| panic!("Could not set up the MongoDB-cache: {}", e)),
+        ),
+        #[cfg(not(feature = "mongo"))]
+        StoreKind::Mongo => panic!("This build has no MongoDB-support; see the 'mongo'-feature"),
     }
-    oldest
 }
 
-fn do_cache() -> bool {
-    env::args().collect::<String>().contains("cache")
+///The watcher of a shared cache; only a MongoDB-cache with cache.mongo.watch set is watched.
+#[cfg(feature = "mongo")]
+pub fn cache_watcher(cache: &CacheConfig) -> Option<CacheWatcher> {
+    if !(cache.enabled && cache.store == StoreKind::Mongo && cache.mongo.watch) {
+        return None;
+    }
+    let store = MongoStore::configured(&cache.mongo)
+        .unwrap_or_else(|e| panic!("Could not set up the MongoDB-cache: {}", e));
+    Some(store.watch(cache.mongo.poll_interval()))
 }
 
-fn ttl() -> Duration {
-    let mut ttl = 8 * 60;
-
-    for attr in env::args().collect::<Vec<String>>() {
-        if attr.contains("ttl") {
-            ttl = i64::from_str(attr.split("=").last().unwrap()).expect(
-                "Please provide the Time-To-Live-in-minutes-parameter e.g. as follows: ttl=480",
-            )
-        }
+///The configured history-store; see [crate::config::HistoryConfig].
+pub fn history_store(config: &Config) -> Box<dyn HistoryStore> {
+    let history = &config.history;
+    match history.store {
+        HistoryKind::File => Box::new(FileHistory::new(&history.file_path)),
+        #[cfg(feature = "mongo")]
+        HistoryKind::Mongo => Box::new(
+            MongoStore::configured(&config.cache.mongo)
+                .unwrap_or_else(|e| panic!("Could not set up the MongoDB-history: {}", e)),
+        ),
+        #[cfg(not(feature = "mongo"))]
+        HistoryKind::Mongo => panic!("This build has no MongoDB-support; see the 'mongo'-feature"),
     }
-    Duration::minutes(ttl)
 }
@@ -0,0 +1,125 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::cache_store::{CacheStore, Result};
+use crate::parse_timeanddate_dot_com::{rfc3339, TimeData};
+
+///The version of the archive-layout written by [export]; [import] only reads this version.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+///The whole cache as moved between machines or attached to a bug report.
+/// A file ending in '.gz' is gzip-compressed JSON, any other file plain JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheArchive {
+    pub version: u32,
+    #[serde(with = "rfc3339")]
+    pub exported_at: DateTime<Utc>,
+    ///The name of the machine the cache was exported on; if known.
+    #[serde(default)]
+    pub exported_from: Option<String>,
+    pub pages: BTreeMap<String, TimeData>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiveError {
+    UnsupportedVersion(u32),
+    ///Pages that are not in the URL list; importing them would only get them pruned.
+    UnknownPages(Vec<String>),
+}
+
+///Writes all cached pages to the file; returns the number of pages.
+pub fn export<P: AsRef<Path>>(store: &dyn CacheStore, file: P) -> Result<usize> {
+    let archive = CacheArchive {
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        exported_from: std::env::var("HOSTNAME").ok(),
+        pages: store.load_all()?.into_iter().collect(),
+    };
+    let writer = BufWriter::new(File::create(file.as_ref())?);
+    if is_compressed(file.as_ref()) {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        serde_json::to_writer(&mut encoder, &archive)?;
+        encoder.finish()?.flush()?;
+    } else {
+        serde_json::to_writer_pretty(writer, &archive)?;
+    }
+    Ok(archive.pages.len())
+}
+
+///Reads and checks an archive without touching any cache.
+pub fn read<P: AsRef<Path>>(file: P) -> Result<CacheArchive> {
+    let reader = BufReader::new(File::open(file.as_ref())?);
+    let reader: Box<dyn Read> = if is_compressed(file.as_ref()) {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+
+    //Check the version before anything else: another version may not even parse...
+    let value: serde_json::Value = serde_json::from_reader(reader)?;
+    let version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or_default() as u32;
+    if version != ARCHIVE_VERSION {
+        return Err(Box::new(ArchiveError::UnsupportedVersion(version)));
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+///Replaces the cache with the archived pages; returns the number of pages.
+/// Every archived page must be in the urls. The pages keep their 'last_updated'-fields: they expire as they would
+/// have in the exported cache, rather than counting as freshly downloaded.
+pub fn import<P: AsRef<Path>>(
+    store: &dyn CacheStore,
+    file: P,
+    urls: &HashMap<String, String>,
+) -> Result<usize> {
+    let archive = read(file)?;
+
+    let unknown: Vec<String> = archive
+        .pages
+        .keys()
+        .filter(|key| !urls.contains_key(*key))
+        .cloned()
+        .collect();
+    if !unknown.is_empty() {
+        return Err(Box::new(ArchiveError::UnknownPages(unknown)));
+    }
+
+    store.restore_all(&archive.pages.clone().into_iter().collect())?;
+    Ok(archive.pages.len())
+}
+
+fn is_compressed(file: &Path) -> bool {
+    file.extension().is_some_and(|e| e == "gz")
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported cache-archive version {}; only version {} can be imported",
+                version, ARCHIVE_VERSION
+            ),
+            ArchiveError::UnknownPages(keys) => write!(
+                f,
+                "The cache-archive holds pages that are not in the URL list: {}",
+                keys.join(", ")
+            ),
+        }
+    }
+}
+
+impl error::Error for ArchiveError {}
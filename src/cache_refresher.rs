@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+#[cfg(feature = "http-fetch")]
+use std::panic;
+#[cfg(feature = "http-fetch")]
+use std::sync::mpsc::RecvTimeoutError;
+#[cfg(any(feature = "http-fetch", feature = "mongo"))]
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+#[cfg(feature = "http-fetch")]
+use std::sync::Mutex;
+#[cfg(feature = "http-fetch")]
+use std::thread;
+#[cfg(feature = "http-fetch")]
+use std::thread::JoinHandle;
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+
+#[cfg(feature = "http-fetch")]
+use crate::cache::{next_expiry, use_cache};
+#[cfg(feature = "http-fetch")]
+use crate::cache_store::CacheStore;
+#[cfg(feature = "http-fetch")]
+use crate::config::Config;
+use crate::parse_timeanddate_dot_com::TimeData;
+
+///What the subscribers receive: all pages, keyed as in 'urls.txt'.
+pub type Snapshot = Arc<HashMap<String, TimeData>>;
+
+///When the refresher wakes up regardless of the pages' expiry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    ///E.g. every 15 minutes.
+    Every(Duration),
+    ///Cron-like: at these times of day [UTC]; e.g. 02:00 and 14:00.
+    DailyAt(Vec<NaiveTime>),
+}
+
+///Keeps the cache up to date in a background thread for long-running UI-sessions and servers:
+/// pages are refreshed as soon as they expire and at every scheduled moment.
+/// Every change is published to the subscribers; dropping the refresher - or calling [CacheRefresher::stop] - stops it.
+#[cfg(feature = "http-fetch")]
+pub struct CacheRefresher {
+    subscribers: Arc<Mutex<Subscribers>>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+///Whoever wants to know about new snapshots; shared with [crate::mongo_api::CacheWatcher].
+#[cfg(any(feature = "http-fetch", feature = "mongo"))]
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    senders: Vec<Sender<Snapshot>>,
+    latest: Option<Snapshot>,
+}
+
+///After a failed refresh - or when stale pages are being served - try again this soon at the earliest.
+#[cfg(feature = "http-fetch")]
+const RETRY_AFTER_MINUTES: i64 = 1;
+
+impl Schedule {
+    ///The first scheduled moment after 'instant'.
+    pub fn next_after(&self, instant: &DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Schedule::Every(interval) => *instant + *interval,
+            Schedule::DailyAt(times) => {
+                let today = instant.date();
+                times
+                    .iter()
+                    .flat_map(|t| vec![today.and_time(*t), today.succ().and_time(*t)])
+                    .flatten()
+                    .filter(|moment| moment > instant)
+                    .min()
+                    //No times at all: once a day then...
+                    .unwrap_or_else(|| *instant + Duration::days(1))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "http-fetch")]
+impl CacheRefresher {
+    ///Refreshes right away and then keeps refreshing until stopped; the urls, their Time To Live and the other
+    /// cache-settings are taken from the configuration.
+    pub fn start(config: Config, store: Box<dyn CacheStore>, schedule: Schedule) -> CacheRefresher {
+        let urls = config.urls();
+        let page_ttls = config.page_ttls();
+        let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+        let (stop, stopped) = channel();
+
+        let publish_to = subscribers.clone();
+        let thread = thread::spawn(move || loop {
+            //A refresh that fails - e.g. nothing could be downloaded at all - or panics must not end the refresher...
+            let refreshed = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                use_cache(&config, store.as_ref(), &urls, &page_ttls)
+            }))
+            .unwrap_or_else(|_| Err("the refresh panicked".into()));
+
+            let now = Utc::now();
+            let retry_at = now + Duration::minutes(RETRY_AFTER_MINUTES);
+            let wake_at = match refreshed {
+                Ok(data) => {
+                    let expiry =
+                        next_expiry(&data, &page_ttls, config.cache.ttl()).map(|e| e.max(retry_at));
+                    publish_to.lock().unwrap().publish(data);
+                    expiry
+                }
+                Err(e) => {
+                    println!(
+                        "Refreshing the cache failed ({}); trying again at {}...",
+                        e, retry_at
+                    );
+                    Some(retry_at)
+                }
+            }
+            .map_or_else(
+                || schedule.next_after(&now),
+                |w| w.min(schedule.next_after(&now)),
+            );
+
+            //Sleeping until the next refresh, unless asked to stop...
+            let sleep = (wake_at - now).to_std().unwrap_or_default();
+            match stopped.recv_timeout(sleep) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        CacheRefresher {
+            subscribers,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    ///Receives every changed snapshot; the latest snapshot - if any - is received right away.
+    pub fn subscribe(&self) -> Receiver<Snapshot> {
+        self.subscribers.lock().unwrap().subscribe()
+    }
+
+    ///Publishes the changes others make to a shared cache as well; e.g. the refreshes of other machines as seen
+    /// by a [crate::mongo_api::CacheWatcher]. Only pages newer than the ones published already are taken.
+    /// Following ends when the updates' channel is closed.
+    pub fn follow(&self, updates: Receiver<Snapshot>) {
+        let publish_to = self.subscribers.clone();
+        thread::spawn(move || {
+            for snapshot in updates {
+                publish_to.lock().unwrap().publish_newer(&snapshot);
+            }
+        });
+    }
+
+    ///Waits for a refresh in progress to finish; the subscribers' channels are closed afterwards.
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            //The thread may have ended already; then there is nobody to tell...
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("The cache-refresher panicked");
+        }
+        self.subscribers.lock().unwrap().close();
+    }
+}
+
+#[cfg(feature = "http-fetch")]
+impl Drop for CacheRefresher {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+#[cfg(any(feature = "http-fetch", feature = "mongo"))]
+impl Subscribers {
+    pub(crate) fn subscribe(&mut self) -> Receiver<Snapshot> {
+        let (sender, receiver) = channel();
+        if let Some(latest) = &self.latest {
+            sender.send(latest.clone()).unwrap();
+        }
+        self.senders.push(sender);
+        receiver
+    }
+
+    ///Only a snapshot that differs from the previous one is sent; subscribers that hung up are forgotten.
+    pub(crate) fn publish(&mut self, data: HashMap<String, TimeData>) {
+        if self
+            .latest
+            .as_ref()
+            .is_some_and(|latest| unchanged(latest, &data))
+        {
+            return;
+        }
+        let snapshot = Arc::new(data);
+        self.senders
+            .retain(|sender| sender.send(snapshot.clone()).is_ok());
+        self.latest = Some(snapshot);
+    }
+
+    ///Publishes the pages that are newer than the latest published ones; e.g. not the stale-marks of a refresh
+    /// that failed, which the cache itself does not know of.
+    #[cfg(feature = "http-fetch")]
+    pub(crate) fn publish_newer(&mut self, data: &HashMap<String, TimeData>) {
+        let merged = data
+            .iter()
+            .map(
+                |(key, td)| match self.latest.as_ref().and_then(|l| l.get(key)) {
+                    Some(latest) if latest.last_updated >= td.last_updated => {
+                        (key.to_string(), latest.clone())
+                    }
+                    _ => (key.to_string(), td.clone()),
+                },
+            )
+            .collect();
+        self.publish(merged);
+    }
+
+    ///Closes the subscribers' channels.
+    pub(crate) fn close(&mut self) {
+        self.senders.clear();
+    }
+}
+
+#[cfg(any(feature = "http-fetch", feature = "mongo"))]
+fn unchanged(old: &HashMap<String, TimeData>, new: &HashMap<String, TimeData>) -> bool {
+    old.len() == new.len()
+        && new.iter().all(|(key, td)| {
+            old.get(key)
+                .is_some_and(|o| o.last_updated == td.last_updated && o.stale == td.stale)
+        })
+}
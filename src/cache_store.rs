@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+
+use crate::parse_timeanddate_dot_com::TimeData;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+///Where the cached time-data lives; the pages are keyed as in 'urls.txt'.
+/// Storing a page stamps its 'last_updated'-field: that is what the cache-expiry is based on.
+pub trait CacheStore: Send + Sync {
+    fn load_all(&self) -> Result<HashMap<String, TimeData>>;
+
+    ///Drops all stored pages first: pages that are no longer downloaded must not linger.
+    fn replace_all(&self, data: &HashMap<String, TimeData>) -> Result<()>;
+
+    ///Same as [CacheStore::replace_all] but the pages are not stamped: they keep their 'last_updated'-fields,
+    /// and thus their age; e.g. when importing an archive.
+    fn restore_all(&self, data: &HashMap<String, TimeData>) -> Result<()>;
+
+    fn get(&self, key: &str) -> Result<Option<TimeData>>;
+
+    fn put(&self, key: &str, data: &TimeData) -> Result<()>;
+
+    ///Stores the pages as [CacheStore::put] does, but a store that can writes them all in one go.
+    fn put_many(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        for (key, td) in data {
+            self.put(key, td)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()>;
+}
+
+///Keeps the cache in a JSON-file; no database required.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+///Keeps the cache for the lifetime of the process only; clones share the same data.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    pages: Arc<Mutex<HashMap<String, TimeData>>>,
+}
+
+pub(crate) fn stamped(data: &TimeData) -> TimeData {
+    TimeData {
+        last_updated: Utc::now(),
+        ..data.clone()
+    }
+}
+
+pub(crate) fn stamped_all(data: &HashMap<String, TimeData>) -> HashMap<String, TimeData> {
+    data.iter()
+        .map(|(k, td)| (k.to_string(), stamped(td)))
+        .collect()
+}
+
+impl FileStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileStore {
+        FileStore { path: path.into() }
+    }
+
+    fn write(&self, pages: &HashMap<String, TimeData>) -> Result<()> {
+        //Write to a temporary file first and then move it in place, so that a crash never leaves a half-written cache...
+        let tmp = self.path.with_extension("tmp");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp)?), pages)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl CacheStore for FileStore {
+    fn load_all(&self) -> Result<HashMap<String, TimeData>> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+        Ok(serde_json::from_reader(BufReader::new(File::open(
+            &self.path,
+        )?))?)
+    }
+
+    fn replace_all(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        self.write(&stamped_all(data))
+    }
+
+    fn restore_all(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        self.write(data)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<TimeData>> {
+        Ok(self.load_all()?.remove(key))
+    }
+
+    fn put(&self, key: &str, data: &TimeData) -> Result<()> {
+        let mut pages = self.load_all()?;
+        pages.insert(key.to_string(), stamped(data));
+        self.write(&pages)
+    }
+
+    fn put_many(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        let mut pages = self.load_all()?;
+        pages.extend(stamped_all(data));
+        self.write(&pages)
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        let mut pages = self.load_all()?;
+        if pages.remove(key).is_some() {
+            self.write(&pages)?;
+        }
+        Ok(())
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        Default::default()
+    }
+
+    ///A store holding these pages as they are; their 'last_updated'-fields are not stamped.
+    pub fn with_pages(pages: HashMap<String, TimeData>) -> MemoryStore {
+        MemoryStore {
+            pages: Arc::new(Mutex::new(pages)),
+        }
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn load_all(&self) -> Result<HashMap<String, TimeData>> {
+        Ok(self.pages.lock().unwrap().clone())
+    }
+
+    fn replace_all(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        self.restore_all(&stamped_all(data))
+    }
+
+    fn restore_all(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        *self.pages.lock().unwrap() = data.clone();
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<TimeData>> {
+        Ok(self.pages.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: &str, data: &TimeData) -> Result<()> {
+        self.pages
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), stamped(data));
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.pages.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
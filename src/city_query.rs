@@ -0,0 +1,169 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, FixedOffset, NaiveTime, Utc};
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::parse_timeanddate_dot_com::{CityData, Sort, TimeData};
+
+///Asking questions of the time-data; e.g. "Which DST-observing cities in Europe currently have office hours?"
+///```
+/// use chrono::NaiveTime;
+/// use rusttimeanddatedotcomparser::city_query::CityQuery;
+/// use rusttimeanddatedotcomparser::parse_timeanddate_dot_com::Sort;
+///
+/// let query = CityQuery::new()
+///     .continent("Europa")
+///     .dst(true)
+///     .local_time_between(NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(17, 0, 0))
+///     .sorted(Sort::ByOffset);
+///```
+/// All filters that have been set must match; an empty query matches every city.
+#[derive(Debug, Clone, Default)]
+pub struct CityQuery {
+    #[cfg(feature = "regex")]
+    pub(crate) name: Option<Regex>,
+    ///What [CityQuery::name_contains] was given; a database can filter on it, it cannot run a Rust-regex.
+    pub(crate) name_part: Option<String>,
+    pub(crate) offset_range: Option<(FixedOffset, FixedOffset)>,
+    pub(crate) dst: Option<bool>,
+    pub(crate) country: Option<String>,
+    pub(crate) continent: Option<String>,
+    local_time_range: Option<(NaiveTime, NaiveTime)>,
+    at: Option<DateTime<Utc>>,
+    sort: Sort,
+}
+
+impl CityQuery {
+    pub fn new() -> CityQuery {
+        Default::default()
+    }
+
+    ///Case-insensitive substring match on the city name.
+    pub fn name_contains(mut self, part: &str) -> Self {
+        #[cfg(feature = "regex")]
+        {
+            self.name = None;
+        }
+        self.name_part = Some(part.to_string());
+        self
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn name_matches(mut self, regex: Regex) -> Self {
+        self.name = Some(regex);
+        self.name_part = None;
+        self
+    }
+
+    ///Both bounds are inclusive.
+    pub fn offset_between(mut self, lowest: FixedOffset, highest: FixedOffset) -> Self {
+        self.offset_range = Some((lowest, highest));
+        self
+    }
+
+    pub fn dst(mut self, is_dls: bool) -> Self {
+        self.dst = Some(is_dls);
+        self
+    }
+
+    ///The country as found in the city's url; e.g. 'new-zealand' or 'New Zealand'.
+    pub fn country(mut self, country: &str) -> Self {
+        self.country = Some(country.trim().replace(" ", "-").to_lowercase());
+        self
+    }
+
+    ///The key of the page in 'urls.txt' the city was scraped from; e.g. 'Europa'.
+    /// On a single (merged) TimeData the source page recorded in each city is used.
+    pub fn continent(mut self, key: &str) -> Self {
+        self.continent = Some(key.trim().to_string());
+        self
+    }
+
+    ///The city's current local time lies between 'from' and 'to', both inclusive.
+    /// When 'to' is earlier than 'from' the range spans midnight; e.g. 22:00 - 02:00.
+    pub fn local_time_between(mut self, from: NaiveTime, to: NaiveTime) -> Self {
+        self.local_time_range = Some((from, to));
+        self
+    }
+
+    ///The instant 'current local time' refers to; defaults to now.
+    pub fn at(mut self, instant: DateTime<Utc>) -> Self {
+        self.at = Some(instant);
+        self
+    }
+
+    pub fn sorted(mut self, sort: Sort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn run(&self, time_data: &TimeData) -> Vec<CityData> {
+        self.collect(vec![(None, time_data)])
+    }
+
+    ///Queries the pages as returned by [crate::cache::fetch_time_data]; cities on more than one page are reported once.
+    pub fn run_on_pages(&self, pages: &HashMap<String, TimeData>) -> Vec<CityData> {
+        self.collect(pages.iter().map(|(k, td)| (Some(k.as_str()), td)).collect())
+    }
+
+    fn collect(&self, pages: Vec<(Option<&str>, &TimeData)>) -> Vec<CityData> {
+        let now = self.at.unwrap_or_else(Utc::now);
+
+        //The BTreeSet orders the cities according to their 'sort'-field...
+        pages
+            .into_iter()
+            .flat_map(|(key, td)| td.city_times.iter().map(move |city| (key, city)))
+            .filter(|(key, city)| {
+                self.continent_matches(key.or_else(|| city.source_page.as_deref()))
+            })
+            .map(|(_, city)| city)
+            .filter(|city| self.city_matches(city, &now))
+            .map(|city| CityData {
+                sort: self.sort,
+                ..city.clone()
+            })
+            .collect::<BTreeSet<CityData>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn continent_matches(&self, key: Option<&str>) -> bool {
+        match (&self.continent, key) {
+            (None, _) => true,
+            (Some(continent), Some(key)) => continent.eq_ignore_ascii_case(key.trim()),
+            (Some(_), None) => false,
+        }
+    }
+
+    fn city_matches(&self, city: &CityData, now: &DateTime<Utc>) -> bool {
+        let name_matches = self.name_part.as_ref().map_or(true, |part| {
+            city.name.to_lowercase().contains(&part.to_lowercase())
+        });
+        #[cfg(feature = "regex")]
+        let name_matches = name_matches
+            && self
+                .name
+                .as_ref()
+                .map_or(true, |regex| regex.is_match(&city.name));
+        let offset = city.utc_offset.get().local_minus_utc();
+        let offset_matches = self.offset_range.map_or(true, |(lowest, highest)| {
+            lowest.local_minus_utc() <= offset && offset <= highest.local_minus_utc()
+        });
+        let dst_matches = self.dst.map_or(true, |is_dls| city.is_dls == is_dls);
+        let country_matches = self.country.as_ref().map_or(true, |country| {
+            city.country()
+                .is_some_and(|c| c.eq_ignore_ascii_case(country))
+        });
+        let local_time_matches = self.local_time_range.map_or(true, |(from, to)| {
+            let local = now.with_timezone(&city.utc_offset.get()).time();
+            if from <= to {
+                from <= local && local <= to
+            } else {
+                from <= local || local <= to
+            }
+        });
+
+        name_matches && offset_matches && dst_matches && country_matches && local_time_matches
+    }
+}
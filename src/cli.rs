@@ -0,0 +1,1025 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic;
+use std::str;
+use std::sync::Arc;
+use std::thread;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Utc};
+
+use crate::cache;
+use crate::cache_archive;
+use crate::cache_refresher::{CacheRefresher, Schedule, Snapshot};
+use crate::cache_store::{CacheStore, MemoryStore, Result};
+use crate::city_query::CityQuery;
+use crate::config::{self, Config};
+#[cfg(feature = "gui")]
+use crate::druid_clock_app;
+#[cfg(feature = "gui")]
+use crate::druid_ui;
+#[cfg(feature = "mongo")]
+use crate::mongo_api::MongoStore;
+use crate::parse_timeanddate_dot_com::{try_download_time_data, ConflictPolicy, Sort, TimeData};
+use crate::snapshot_diff;
+use crate::time_conversion::{convert_by_name, TimeInput};
+
+///The command did what it was asked to do.
+pub const EXIT_OK: i32 = 0;
+///The command failed; e.g. a page could not be downloaded or a city is unknown.
+/// For `diff` it means that there are differences, as with diff(1).
+pub const EXIT_FAILURE: i32 = 1;
+///The command-line or the configuration is wrong; nothing was done.
+pub const EXIT_USAGE: i32 = 2;
+
+///The port `serve` listens on when none is given.
+pub const DEFAULT_PORT: u16 = 8080;
+
+///What the program is asked to do; the settings - 'setting=value' and use_cache - are left to [config::Config].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    ///No command at all: the city-windows followed by the clock and the animation, as configured in [config::UiConfig].
+    #[cfg(feature = "gui")]
+    Windows,
+    Fetch,
+    List(ListOptions),
+    #[cfg(feature = "gui")]
+    Show,
+    #[cfg(feature = "gui")]
+    Clock,
+    Convert {
+        time: TimeInput,
+        from: String,
+        to: String,
+    },
+    Diff {
+        old: Source,
+        new: Source,
+    },
+    Cache(CacheCommand),
+    Serve {
+        port: u16,
+        ///How often the pages are refreshed regardless of their expiry; the cache's Time To Live if not given.
+        every: Option<Duration>,
+    },
+    ///The help of the program, or of one command.
+    Help(Option<String>),
+}
+
+impl Command {
+    ///Whether the command reads the URL list; e.g. to download the pages in it.
+    pub fn fetches(&self) -> bool {
+        match self {
+            #[cfg(feature = "gui")]
+            Command::Windows | Command::Show => true,
+            #[cfg(feature = "gui")]
+            Command::Clock => false,
+            Command::Fetch | Command::List(_) | Command::Convert { .. } | Command::Serve { .. } => {
+                true
+            }
+            Command::Diff { old, new } => *old == Source::Web || *new == Source::Web,
+            Command::Cache(cache_command) => matches!(cache_command, CacheCommand::Import(_)),
+            Command::Help(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheCommand {
+    Clear,
+    Export(String),
+    Import(String),
+    #[cfg(feature = "mongo")]
+    Migrate,
+}
+
+///Where `diff` takes a snapshot of the time-data from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    ///The configured cache-store.
+    Cache,
+    ///Downloaded right now.
+    Web,
+    ///A file written by `cache export`.
+    Archive(String),
+}
+
+///The filters of `list`; see [CityQuery].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListOptions {
+    pub continent: Option<String>,
+    pub name: Option<String>,
+    pub country: Option<String>,
+    pub offset: Option<FixedOffset>,
+    pub dst: Option<bool>,
+    pub sort: Option<Sort>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    UnknownCommand(String),
+    UnknownOption {
+        command: String,
+        option: String,
+    },
+    MissingArgument {
+        command: String,
+        argument: String,
+    },
+    UnexpectedArgument {
+        command: String,
+        argument: String,
+    },
+    InvalidValue {
+        option: String,
+        value: String,
+        expected: String,
+    },
+    ///The command needs a cargo-feature this build was made without.
+    Unavailable {
+        command: String,
+        feature: String,
+    },
+}
+
+const USAGE: &str = "\
+Usage: rusttimeanddatedotcomparser [COMMAND] [ARGUMENTS] [setting=value ...]
+
+Commands:
+  fetch      Download every page now and store it in the cache
+  list       List the cities, their UTC-offsets and local times
+  show       Show the cities in a window per page
+  clock      Show the clock
+  convert    Convert a time from one city to another
+  diff       Compare two snapshots of the time-data
+  cache      Clear, export or import the cache
+  serve      Keep the cache fresh and serve the time-data as JSON over HTTP
+  help       Show this help, or the help of a command
+
+Without a command the city-windows, the clock and the animation are shown; see the [ui]-settings.
+Settings - e.g. use_cache, ttl=120 or cache.store=sqlite - go anywhere on the command-line; see timeanddate.toml.
+
+Exit codes: 0 = done, 1 = the command failed, 2 = wrong command-line or configuration.";
+
+///The help of a command; None for an unknown command.
+pub fn help(command: &str) -> Option<&'static str> {
+    let help = match command {
+        "fetch" => {
+            "\
+Usage: fetch
+
+Downloads every page now - regardless of the cache's Time To Live - and stores it in the cache when caching is
+enabled. Exits with 1 when any page could not be downloaded or stored."
+        }
+        "list" => {
+            "\
+Usage: list [--continent KEY] [--name PART] [--country NAME] [--offset +HH:MM] [--dst | --no-dst] [--sort name|offset]
+
+Lists the cities - from the cache when caching is enabled - with their UTC-offset, DST and local time.
+  --continent KEY   Only the cities of this page of the URL list; e.g. Europa
+  --name PART       Only the cities whose name contains this; case-insensitive
+  --country NAME    Only the cities in this country
+  --offset +HH:MM   Only the cities with this UTC-offset
+  --dst, --no-dst   Only the cities (not) observing DST right now
+  --sort name|offset
+Exits with 1 when no city matches."
+        }
+        "show" => {
+            "\
+Usage: show
+
+Shows the cities in a window per page; the windows follow the cache when cache.mongo.watch is set."
+        }
+        "clock" => {
+            "\
+Usage: clock
+
+Shows the clock."
+        }
+        "convert" => {
+            "\
+Usage: convert TIME FROM TO
+
+Converts a time in the city FROM to the city TO; city-names are case-insensitive, e.g. 'new york'.
+TIME is 'now', an instant - e.g. 2020-08-21T15:00:00Z - or the local time in FROM: '2020-08-21 15:00' or 15:00 (today).
+Exits with 1 when a city is unknown or the local time does not exist in FROM."
+        }
+        "diff" => {
+            "\
+Usage: diff [OLD [NEW]]
+
+Shows the cities added, removed or changed between two snapshots; each is 'cache', 'web' - downloaded now - or a
+file written by 'cache export'. OLD is the cache and NEW the web when left out.
+Exits with 0 when there are no differences, 1 when there are and 2 when a snapshot could not be read."
+        }
+        "cache" => {
+            if cfg!(feature = "mongo") {
+                "\
+Usage: cache clear | cache export FILE | cache import FILE | cache migrate
+
+  clear         Removes all pages from the configured cache
+  export FILE   Writes all cached pages to FILE; gzip-compressed when FILE ends in .gz
+  import FILE   Replaces the cache with the pages in FILE; they must all be in the URL list
+  migrate       Upgrades the documents of the MongoDB-cache to the current schema-version;
+                exits with 1 when some documents are unreadable"
+            } else {
+                "\
+Usage: cache clear | cache export FILE | cache import FILE
+
+  clear         Removes all pages from the configured cache
+  export FILE   Writes all cached pages to FILE; gzip-compressed when FILE ends in .gz
+  import FILE   Replaces the cache with the pages in FILE; they must all be in the URL list"
+            }
+        }
+        "serve" => {
+            "\
+Usage: serve [--port PORT] [--every MINUTES]
+
+Keeps the cache fresh in the background and serves the time-data on http://localhost:PORT (8080):
+  GET /       All pages, keyed as in the URL list
+  GET /KEY    One page; e.g. /Europa
+  --every MINUTES   Also refresh this often; by default every cache.ttl_minutes"
+        }
+        "help" => {
+            "\
+Usage: help [COMMAND]
+
+Shows the help of the program, or of the command."
+        }
+        _ => return None,
+    };
+    Some(help)
+}
+
+///The help of the program.
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+///Parses the program arguments - without the program itself - into a command.
+/// Settings - 'setting=value' and the use_cache short-hand - may go anywhere and are skipped.
+pub fn parse(args: &[String]) -> std::result::Result<Command, CliError> {
+    let mut args = args
+        .iter()
+        .filter(|a| !is_setting(a))
+        .map(String::as_str)
+        .peekable();
+
+    let command = match args.next() {
+        Some(command) => command,
+        #[cfg(feature = "gui")]
+        None => return Ok(Command::Windows),
+        #[cfg(not(feature = "gui"))]
+        None => return Ok(Command::Help(None)),
+    };
+    if command == "--help" || command == "-h" {
+        return Ok(Command::Help(None));
+    }
+    let mut arguments = Arguments {
+        command: command.to_string(),
+        args,
+    };
+    //'list --help' or 'cache export --help'; the help of an unknown command is an error...
+    if arguments.has_flag("--help") || arguments.has_flag("-h") {
+        return match help(command) {
+            Some(_) => Ok(Command::Help(Some(command.to_string()))),
+            None => Err(CliError::UnknownCommand(command.to_string())),
+        };
+    }
+
+    let parsed = match command {
+        "fetch" => Command::Fetch,
+        "list" => Command::List(arguments.list_options()?),
+        #[cfg(feature = "gui")]
+        "show" => Command::Show,
+        #[cfg(feature = "gui")]
+        "clock" => Command::Clock,
+        #[cfg(not(feature = "gui"))]
+        "show" | "clock" => return Err(unavailable(command, "gui")),
+        "convert" => Command::Convert {
+            time: parse_time(&arguments.required("TIME")?)?,
+            from: arguments.required("FROM")?,
+            to: arguments.required("TO")?,
+        },
+        "diff" => Command::Diff {
+            old: arguments.optional().map_or(Source::Cache, Source::from),
+            new: arguments.optional().map_or(Source::Web, Source::from),
+        },
+        "cache" => Command::Cache(match arguments.required("clear|export|import")?.as_str() {
+            "clear" => CacheCommand::Clear,
+            "export" => CacheCommand::Export(arguments.required("FILE")?),
+            "import" => CacheCommand::Import(arguments.required("FILE")?),
+            #[cfg(feature = "mongo")]
+            "migrate" => CacheCommand::Migrate,
+            #[cfg(not(feature = "mongo"))]
+            "migrate" => return Err(unavailable("cache migrate", "mongo")),
+            other => {
+                return Err(CliError::UnexpectedArgument {
+                    command: arguments.command,
+                    argument: other.to_string(),
+                })
+            }
+        }),
+        "serve" => arguments.serve_options()?,
+        "help" => match arguments.optional() {
+            Some(topic) if help(&topic).is_none() => return Err(CliError::UnknownCommand(topic)),
+            topic => Command::Help(topic),
+        },
+        _ => return Err(CliError::UnknownCommand(command.to_string())),
+    };
+    arguments.finish()?;
+    Ok(parsed)
+}
+
+///Writes the warnings of the crate - e.g. about the unreadable documents that are skipped - to stderr.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+///Runs the command on the command-line and returns the exit code; see [EXIT_OK], [EXIT_FAILURE] and [EXIT_USAGE].
+pub fn run(args: &[String], test_mode: bool) -> i32 {
+    //A logger is set once per process; an application embedding the crate sets its own...
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    let command = match parse(args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    if let Command::Help(topic) = &command {
+        println!("{}", topic.as_deref().and_then(help).unwrap_or(USAGE));
+        return EXIT_OK;
+    }
+
+    //A configuration that does not add up is reported before anything else happens...
+    let env_vars: Vec<(String, String)> = env::vars().collect();
+    let config = match config::Config::load(args, &env_vars) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_USAGE;
+        }
+    };
+    //Only the commands that download pages need the URL list...
+    if command.fetches() {
+        if let Err(e) = config.validate_urls() {
+            eprintln!("{}", e);
+            return EXIT_USAGE;
+        }
+    }
+
+    //Much of the crate panics on what it cannot handle; that is a failed command all the same...
+    panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        execute(command, &config, test_mode)
+    }))
+    .unwrap_or(EXIT_FAILURE)
+}
+
+//Only the windows care about the test-mode...
+#[cfg_attr(not(feature = "gui"), allow(unused_variables))]
+fn execute(command: Command, config: &Config, test_mode: bool) -> i32 {
+    match command {
+        #[cfg(feature = "gui")]
+        Command::Windows => {
+            if show(config, test_mode) != EXIT_OK {
+                return EXIT_FAILURE;
+            }
+            let ui = &config.ui;
+            if ui.show_clock {
+                druid_clock_app::show(test_mode);
+            }
+            if ui.show_animation {
+                crate::animation::show_animation(test_mode);
+            }
+            EXIT_OK
+        }
+        Command::Fetch => fetch(config),
+        Command::List(options) => list(config, &options),
+        #[cfg(feature = "gui")]
+        Command::Show => show(config, test_mode),
+        #[cfg(feature = "gui")]
+        Command::Clock => {
+            druid_clock_app::show(test_mode);
+            EXIT_OK
+        }
+        Command::Convert { time, from, to } => convert(config, time, &from, &to),
+        Command::Diff { old, new } => diff(config, &old, &new),
+        Command::Cache(cache_command) => cache_command.execute(config),
+        Command::Serve { port, every } => serve(config, port, every),
+        Command::Help(_) => EXIT_OK,
+    }
+}
+
+#[cfg(feature = "gui")]
+fn show(config: &Config, test_mode: bool) -> i32 {
+    let time_data = match fetch_time_data(config) {
+        Some(time_data) => time_data,
+        None => return EXIT_FAILURE,
+    };
+    #[cfg(feature = "mongo")]
+    match cache::cache_watcher(&config.cache) {
+        Some(watcher) => druid_ui::show_live(time_data, test_mode, &|| watcher.subscribe()),
+        None => druid_ui::show(time_data, test_mode),
+    }
+    #[cfg(not(feature = "mongo"))]
+    druid_ui::show(time_data, test_mode);
+    EXIT_OK
+}
+
+///The time-data for the commands that show it; why there is none is told on stderr.
+fn fetch_time_data(config: &Config) -> Option<HashMap<String, TimeData>> {
+    cache::fetch_time_data(config)
+        .map_err(|e| eprintln!("Could not get the time-data: {}", e))
+        .ok()
+}
+
+fn fetch(config: &Config) -> i32 {
+    let store = if config.cache.enabled {
+        Some(cache::cache_store(&config.cache))
+    } else {
+        None
+    };
+
+    let mut failures = 0;
+    let mut fresh = HashMap::new();
+    let downloaded: BTreeMap<String, Result<TimeData>> =
+        try_download_time_data(config.fetch_options(), &config.urls())
+            .into_iter()
+            .collect();
+    for (key, page) in downloaded {
+        match page {
+            Ok(td) => {
+                println!("{}: {} cities", key, td.city_times.len());
+                fresh.insert(key, td);
+            }
+            Err(e) => {
+                eprintln!("{}: {}", key, e);
+                failures += 1;
+            }
+        }
+    }
+    //All downloaded pages are stored in one go...
+    if let Some(store) = &store {
+        if let Err(e) = store.put_many(&fresh) {
+            eprintln!("Could not store the time-data: {}", e);
+            return EXIT_FAILURE;
+        }
+    }
+    if store.is_some() && config.history.enabled && !fresh.is_empty() {
+        cache::record_history(
+            cache::history_store(config).as_ref(),
+            &config.history.retention(),
+            &fresh,
+        );
+    }
+    if failures == 0 {
+        EXIT_OK
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+fn list(config: &Config, options: &ListOptions) -> i32 {
+    let mut query = CityQuery::new().sorted(options.sort.unwrap_or(config.fetch.sort));
+    if let Some(continent) = &options.continent {
+        query = query.continent(continent);
+    }
+    if let Some(name) = &options.name {
+        query = query.name_contains(name);
+    }
+    if let Some(country) = &options.country {
+        query = query.country(country);
+    }
+    if let Some(offset) = options.offset {
+        query = query.offset_between(offset, offset);
+    }
+    if let Some(is_dls) = options.dst {
+        query = query.dst(is_dls);
+    }
+
+    let now = Utc::now();
+    let pages = match fetch_time_data(config) {
+        Some(pages) => pages,
+        None => return EXIT_FAILURE,
+    };
+    let cities = query.run_on_pages(&pages);
+    for city in cities.iter() {
+        let offset = city.utc_offset.get();
+        println!(
+            "{:<32} {} {:<3} {}",
+            city.name,
+            offset,
+            if city.is_dls { "DST" } else { "" },
+            now.with_timezone(&offset).format("%a %H:%M")
+        );
+    }
+    if cities.is_empty() {
+        eprintln!("No city matches.");
+        return EXIT_FAILURE;
+    }
+    EXIT_OK
+}
+
+fn convert(config: &Config, time: TimeInput, from: &str, to: &str) -> i32 {
+    let pages = match fetch_time_data(config) {
+        Some(pages) => pages,
+        None => return EXIT_FAILURE,
+    };
+    //The newest page wins; the user is told which offsets were not taken...
+    let (merged, conflicts) =
+        TimeData::merge_resolving(pages.iter(), &ConflictPolicy::PreferNewest);
+    for conflict in conflicts.iter() {
+        eprintln!("{}", conflict);
+    }
+
+    match convert_by_name(time, &merged, from, to) {
+        Ok(c) => {
+            let format = "%a %Y-%m-%d %H:%M %:z";
+            println!(
+                "{} {} = {} {}",
+                c.from.format(format),
+                from.trim(),
+                c.to.format(format),
+                to.trim()
+            );
+            if c.day_rollover != 0 {
+                println!("{:+} day(s) in {}", c.day_rollover, to.trim());
+            }
+            if let Some(later) = c.later_alternative {
+                println!(
+                    "The time occurs twice in {}; the second time it is {} in {}",
+                    from.trim(),
+                    later.format(format),
+                    to.trim()
+                );
+            }
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn diff(config: &Config, old: &Source, new: &Source) -> i32 {
+    match (old.load(config), new.load(config)) {
+        (Ok(old), Ok(new)) => {
+            let diff = snapshot_diff::diff(&old, &new);
+            println!("{}", diff);
+            if diff.is_empty() {
+                EXIT_OK
+            } else {
+                EXIT_FAILURE
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Could not read the time-data: {}", e);
+            EXIT_USAGE
+        }
+    }
+}
+
+fn serve(config: &Config, port: u16, every: Option<Duration>) -> i32 {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not listen on port {}: {}", port, e);
+            return EXIT_FAILURE;
+        }
+    };
+    //Without a cache the time-data is only kept for as long as the server runs...
+    let store: Box<dyn CacheStore> = if config.cache.enabled {
+        cache::cache_store(&config.cache)
+    } else {
+        Box::new(MemoryStore::new())
+    };
+    let refresher = CacheRefresher::start(
+        config.clone(),
+        store,
+        Schedule::Every(every.unwrap_or_else(|| config.cache.ttl())),
+    );
+    //Other machines sharing a watched MongoDB-cache refresh it as well...
+    #[cfg(feature = "mongo")]
+    let watcher = cache::cache_watcher(&config.cache);
+    #[cfg(feature = "mongo")]
+    if let Some(watcher) = &watcher {
+        refresher.follow(watcher.subscribe());
+    }
+    let updates = refresher.subscribe();
+    let mut latest: Snapshot = Arc::new(HashMap::new());
+
+    println!("Serving the time-data on http://localhost:{}/ ...", port);
+    for stream in listener.incoming() {
+        if let Some(snapshot) = updates.try_iter().last() {
+            latest = snapshot;
+        }
+        //A slow client holds up its own connection only...
+        let pages = latest.clone();
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(e) = answer(stream, &pages) {
+                        println!("Could not answer a request: {}", e);
+                    }
+                });
+            }
+            Err(e) => println!("Could not accept a connection: {}", e),
+        }
+    }
+    EXIT_OK
+}
+
+///How long `serve` waits for a client to send its request.
+pub const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+///Answers one request of `serve`; the request-headers are read up to the blank line, but not used.
+pub fn answer(mut stream: TcpStream, pages: &HashMap<String, TimeData>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => respond(path, pages),
+        _ => (405, String::from("{\"error\":\"Only GET is supported\"}")),
+    };
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+///The status and JSON-body `serve` answers a GET of this path with: '/' for all pages or '/KEY' for one page.
+pub fn respond(path: &str, pages: &HashMap<String, TimeData>) -> (u16, String) {
+    let error =
+        |status: u16, message: &str| (status, serde_json::json!({ "error": message }).to_string());
+    if pages.is_empty() {
+        return error(503, "The time-data has not been fetched yet");
+    }
+    //Keys like 'Popular Cities' arrive as 'Popular%20Cities'...
+    let key = percent_decode(path.split('?').next().unwrap_or_default());
+    let key = key.trim_matches('/');
+    if key.is_empty() {
+        let sorted: BTreeMap<&String, &TimeData> = pages.iter().collect();
+        return (200, serde_json::to_string(&sorted).unwrap());
+    }
+    match pages.get(key) {
+        Some(td) => (200, serde_json::to_string(td).unwrap()),
+        None => error(404, &format!("No page '{}'", key)),
+    }
+}
+
+///Replaces every '%XX' by the byte it stands for; anything that is no such escape is left as it is.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+impl CacheCommand {
+    fn execute(&self, config: &Config) -> i32 {
+        let store = || cache::cache_store(&config.cache);
+        let done = match self {
+            CacheCommand::Clear => store()
+                .replace_all(&HashMap::new())
+                .map(|_| String::from("Cleared the cache.")),
+            CacheCommand::Export(file) => cache_archive::export(store().as_ref(), file)
+                .map(|n| format!("Exported {} pages to {}.", n, file)),
+            CacheCommand::Import(file) => {
+                cache_archive::import(store().as_ref(), file, &config.urls())
+                    .map(|n| format!("Imported {} pages from {}.", n, file))
+            }
+            #[cfg(feature = "mongo")]
+            CacheCommand::Migrate => {
+                return match MongoStore::configured(&config.cache.mongo)
+                    .and_then(|s| s.migrate_stored_data())
+                {
+                    Ok(report) => {
+                        println!(
+                            "Migrated {} documents; {} unreadable documents were left untouched: {:?} {:?}",
+                            report.migrated,
+                            report.unreadable.len() + report.unreadable_cities.len(),
+                            report.unreadable,
+                            report.unreadable_cities
+                        );
+                        if report.unreadable.is_empty() && report.unreadable_cities.is_empty() {
+                            EXIT_OK
+                        } else {
+                            EXIT_FAILURE
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Could not migrate the MongoDB-cache: {}", e);
+                        EXIT_FAILURE
+                    }
+                }
+            }
+        };
+        match done {
+            Ok(message) => {
+                println!("{}", message);
+                EXIT_OK
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                EXIT_FAILURE
+            }
+        }
+    }
+}
+
+impl Source {
+    fn load(&self, config: &Config) -> Result<HashMap<String, TimeData>> {
+        match self {
+            Source::Cache => cache::cache_store(&config.cache).load_all(),
+            Source::Web => try_download_time_data(config.fetch_options(), &config.urls())
+                .into_iter()
+                .map(|(key, page)| page.map(|td| (key, td)))
+                .collect(),
+            Source::Archive(file) => Ok(cache_archive::read(file)?.pages.into_iter().collect()),
+        }
+    }
+}
+
+impl From<String> for Source {
+    fn from(source: String) -> Self {
+        match source.as_str() {
+            "cache" => Source::Cache,
+            "web" => Source::Web,
+            _ => Source::Archive(source),
+        }
+    }
+}
+
+///The arguments following the command; options are '--name value' or '--name=value'.
+struct Arguments<'a, I: Iterator<Item = &'a str>> {
+    command: String,
+    args: std::iter::Peekable<I>,
+}
+
+impl<'a, I: Iterator<Item = &'a str> + Clone> Arguments<'a, I> {
+    fn has_flag(&self, flag: &str) -> bool {
+        self.args.clone().any(|a| a == flag)
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Arguments<'a, I> {
+    ///The next positional argument, if any.
+    fn optional(&mut self) -> Option<String> {
+        match self.args.peek() {
+            Some(a) if !a.starts_with("--") => self.args.next().map(String::from),
+            _ => None,
+        }
+    }
+
+    fn required(&mut self, argument: &str) -> std::result::Result<String, CliError> {
+        self.optional().ok_or_else(|| CliError::MissingArgument {
+            command: self.command.to_string(),
+            argument: argument.to_string(),
+        })
+    }
+
+    ///The value of the option when it is the next argument.
+    fn option(&mut self, name: &str) -> std::result::Result<Option<String>, CliError> {
+        match self.args.peek() {
+            Some(a) if *a == name => {
+                self.args.next();
+                self.required(name).map(Some)
+            }
+            Some(a) if a.starts_with(&format!("{}=", name)) => {
+                Ok(self.args.next().map(|a| a[name.len() + 1..].to_string()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        self.args.next_if(|a| *a == name).is_some()
+    }
+
+    fn list_options(&mut self) -> std::result::Result<ListOptions, CliError> {
+        let mut options = ListOptions::default();
+        while let Some(&next) = self.args.peek() {
+            if let Some(continent) = self.option("--continent")? {
+                options.continent = Some(continent);
+            } else if let Some(name) = self.option("--name")? {
+                options.name = Some(name);
+            } else if let Some(country) = self.option("--country")? {
+                options.country = Some(country);
+            } else if let Some(offset) = self.option("--offset")? {
+                options.offset = Some(parse_offset(&offset).ok_or(CliError::InvalidValue {
+                    option: String::from("--offset"),
+                    value: offset,
+                    expected: String::from("an offset like +05:30 or -3"),
+                })?);
+            } else if self.flag("--dst") {
+                options.dst = Some(true);
+            } else if self.flag("--no-dst") {
+                options.dst = Some(false);
+            } else if let Some(sort) = self.option("--sort")? {
+                options.sort = Some(match sort.as_str() {
+                    "name" => Sort::ByName,
+                    "offset" => Sort::ByOffset,
+                    _ => {
+                        return Err(CliError::InvalidValue {
+                            option: String::from("--sort"),
+                            value: sort,
+                            expected: String::from("name or offset"),
+                        })
+                    }
+                });
+            } else {
+                return Err(self.unexpected(next));
+            }
+        }
+        Ok(options)
+    }
+
+    fn serve_options(&mut self) -> std::result::Result<Command, CliError> {
+        let (mut port, mut every) = (DEFAULT_PORT, None);
+        while let Some(&next) = self.args.peek() {
+            if let Some(p) = self.option("--port")? {
+                port = p.parse().map_err(|_| CliError::InvalidValue {
+                    option: String::from("--port"),
+                    value: p,
+                    expected: String::from("a port number"),
+                })?;
+            } else if let Some(minutes) = self.option("--every")? {
+                every = match minutes.parse::<i64>() {
+                    Ok(m) if m > 0 => Some(Duration::minutes(m)),
+                    _ => {
+                        return Err(CliError::InvalidValue {
+                            option: String::from("--every"),
+                            value: minutes,
+                            expected: String::from("a positive number of minutes"),
+                        })
+                    }
+                };
+            } else {
+                return Err(self.unexpected(next));
+            }
+        }
+        Ok(Command::Serve { port, every })
+    }
+
+    ///Anything left over is an error.
+    fn finish(&mut self) -> std::result::Result<(), CliError> {
+        match self.args.next() {
+            Some(a) => Err(self.unexpected(a)),
+            None => Ok(()),
+        }
+    }
+
+    fn unexpected(&self, argument: &str) -> CliError {
+        if argument.starts_with('-') {
+            CliError::UnknownOption {
+                command: self.command.to_string(),
+                option: argument.to_string(),
+            }
+        } else {
+            CliError::UnexpectedArgument {
+                command: self.command.to_string(),
+                argument: argument.to_string(),
+            }
+        }
+    }
+}
+
+fn unavailable(command: &str, feature: &str) -> CliError {
+    CliError::Unavailable {
+        command: command.to_string(),
+        feature: feature.to_string(),
+    }
+}
+
+///See [config::Config::from_sources]; an option like --port=8080 is no setting.
+fn is_setting(arg: &str) -> bool {
+    arg == "use_cache" || (arg.contains('=') && !arg.starts_with('-'))
+}
+
+fn parse_time(time: &str) -> std::result::Result<TimeInput, CliError> {
+    let time = time.trim();
+    if time == "now" {
+        return Ok(TimeInput::Instant(Utc::now()));
+    }
+    if let Ok(instant) = DateTime::parse_from_rfc3339(time) {
+        return Ok(TimeInput::Instant(instant.with_timezone(&Utc)));
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"].iter() {
+        if let Ok(local) = NaiveDateTime::parse_from_str(time, format) {
+            return Ok(TimeInput::Local(local));
+        }
+    }
+    //Just the time: today's...
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    NaiveDateTime::parse_from_str(&format!("{} {}", today, time), "%Y-%m-%d %H:%M")
+        .map(TimeInput::Local)
+        .map_err(|_| CliError::InvalidValue {
+            option: String::from("TIME"),
+            value: time.to_string(),
+            expected: String::from("now, 2020-08-21T15:00:00Z, '2020-08-21 15:00' or 15:00"),
+        })
+}
+
+///E.g. +05:30, -03:00, +1 or 0.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let offset = offset.trim();
+    let (sign, rest) = match offset.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownCommand(command) => write!(f, "Unknown command '{}'", command),
+            CliError::UnknownOption { command, option } => {
+                write!(f, "Unknown option '{}' for '{}'", option, command)
+            }
+            CliError::MissingArgument { command, argument } => {
+                write!(f, "Missing argument {} for '{}'", argument, command)
+            }
+            CliError::UnexpectedArgument { command, argument } => {
+                write!(f, "Unexpected argument '{}' for '{}'", argument, command)
+            }
+            CliError::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value '{}' for {}: expected {}",
+                value, option, expected
+            ),
+            CliError::Unavailable { command, feature } => write!(
+                f,
+                "'{}' needs the '{}'-feature; this build was made without it",
+                command, feature
+            ),
+        }
+    }
+}
+
+impl error::Error for CliError {}
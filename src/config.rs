@@ -0,0 +1,614 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use toml::Value;
+
+use crate::cache::{page_options_from, page_ttls_from, urls_from};
+use crate::history::Retention;
+use crate::parse_timeanddate_dot_com::{FetchOptions, Sort};
+use crate::url_list;
+use crate::url_list::UrlListError;
+
+///The configuration-file used when none is given with config=/some/path or the TIMEANDDATE_CONFIG-variable.
+pub const DEFAULT_CONFIG_FILE: &str = "timeanddate.toml";
+
+///Every setting can be overridden by an environment-variable: 'TIMEANDDATE_' followed by the
+/// setting's name in capitals with dots replaced by underscores; e.g. TIMEANDDATE_CACHE_TTL_MINUTES=120.
+pub const ENV_PREFIX: &str = "TIMEANDDATE_";
+
+///The TIMEANDDATE_-variables that are no setting: the configuration-file and the MongoDB the integration-tests
+/// run against. Any other unknown TIMEANDDATE_-variable is an error.
+pub const OTHER_ENV_VARS: &[&str] = &["TIMEANDDATE_CONFIG", "TIMEANDDATE_TEST_MONGO_URI"];
+
+///The MongoDB-server used when cache.mongo.uri is not set: a LOCAL MONGODB.
+pub const DEFAULT_MONGO_URI: &str = "mongodb://localhost:27017";
+
+///All settings; the defaults apply to whatever the configuration-file leaves out.
+///```toml
+/// urls_file = "urls.txt"
+///
+/// [cache]
+/// enabled = true
+/// store = "sqlite"
+/// ttl_minutes = 120
+///
+/// [fetch]
+/// timeout_seconds = 10
+///```
+/// Settings can be overridden on the command-line as well, e.g. `cargo run cache.ttl_minutes=120`;
+/// the command-line wins from the environment which wins from the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    ///The URL list; see [crate::url_list::read].
+    pub urls_file: String,
+    ///Urls given in the configuration itself - key = "url" - replace the URL list.
+    pub urls: BTreeMap<String, String>,
+    pub cache: CacheConfig,
+    pub history: HistoryConfig,
+    pub fetch: FetchConfig,
+    pub ui: UiConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub store: StoreKind,
+    ///The global Time To Live [minutes]; the URL list can set one per page.
+    pub ttl_minutes: i64,
+    ///How old cached time-data may be to still be served when refreshing it fails [minutes].
+    pub max_stale_minutes: i64,
+    pub file_path: String,
+    pub sqlite_path: String,
+    pub mongo: MongoConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    Mongo,
+    File,
+    Sqlite,
+    Memory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MongoConfig {
+    ///The connection string; credentials, replica-set and TLS-options included.
+    pub uri: String,
+    pub database: String,
+    pub collection: String,
+    ///Follow the changes other processes make to the cache; see [crate::mongo_api::MongoStore::watch].
+    pub watch: bool,
+    ///How often the cache is polled when change streams are not available [seconds].
+    pub poll_seconds: u64,
+}
+
+///Every refresh of the cache is appended to the history; see [crate::history::HistoryStore].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    pub store: HistoryKind,
+    pub file_path: String,
+    ///How old a snapshot may get [days]; zero keeps them forever.
+    pub max_age_days: i64,
+    ///How many snapshots are kept at most; zero keeps them all.
+    pub max_snapshots: usize,
+}
+
+///The Mongo-history lives next to the Mongo-cache; it uses the cache.mongo-settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Mongo,
+    File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchConfig {
+    pub sort: Sort,
+    ///How long downloading one page may take [seconds].
+    pub timeout_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    pub show_clock: bool,
+    pub show_animation: bool,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Unreadable {
+        file: String,
+        error: String,
+    },
+    ///The file is no valid TOML or holds unknown settings or values of the wrong type.
+    Malformed {
+        origin: String,
+        error: String,
+    },
+    UnknownSetting {
+        origin: String,
+        setting: String,
+    },
+    InvalidValue {
+        setting: String,
+        value: String,
+        expected: String,
+    },
+    InvalidUrlList(UrlListError),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            urls_file: String::from("urls.txt"),
+            urls: BTreeMap::new(),
+            cache: Default::default(),
+            history: Default::default(),
+            fetch: Default::default(),
+            ui: Default::default(),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: false,
+            store: StoreKind::Mongo,
+            ttl_minutes: 8 * 60,
+            max_stale_minutes: 24 * 60,
+            file_path: String::from("time_data_cache.json"),
+            sqlite_path: String::from("time_data_cache.sqlite"),
+            mongo: Default::default(),
+        }
+    }
+}
+
+impl Default for MongoConfig {
+    fn default() -> Self {
+        MongoConfig {
+            uri: String::from(DEFAULT_MONGO_URI),
+            database: String::from("test"),
+            collection: String::from("city_data"),
+            watch: false,
+            poll_seconds: 60,
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            enabled: false,
+            store: HistoryKind::File,
+            file_path: String::from("time_data_history.jsonl"),
+            max_age_days: 365,
+            max_snapshots: 0,
+        }
+    }
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            sort: Sort::ByName,
+            timeout_seconds: 30,
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            show_clock: true,
+            show_animation: true,
+        }
+    }
+}
+
+impl Config {
+    ///From the configuration-file - if any - the TIMEANDDATE_-variables and the program arguments; e.g. those of
+    /// the process, as the command-line interface does. The file is config=/some/path, TIMEANDDATE_CONFIG or
+    /// timeanddate.toml when it exists.
+    pub fn load(args: &[String], env_vars: &[(String, String)]) -> Result<Config, ConfigError> {
+        let config_var = format!("{}CONFIG", ENV_PREFIX);
+        let file = args
+            .iter()
+            .filter_map(|a| a.strip_prefix("config="))
+            .map(|f| f.trim().to_string())
+            .next_back()
+            .or_else(|| {
+                env_vars
+                    .iter()
+                    .find(|(name, _)| *name == config_var)
+                    .map(|(_, v)| v.to_string())
+            });
+
+        let (file, required) = match file {
+            Some(f) => (f, true),
+            None => (String::from(DEFAULT_CONFIG_FILE), false),
+        };
+        let toml = match fs::read_to_string(&file) {
+            Ok(toml) => Some(toml),
+            Err(_) if !required && !Path::new(&file).exists() => None,
+            Err(e) => {
+                return Err(ConfigError::Unreadable {
+                    file,
+                    error: e.to_string(),
+                })
+            }
+        };
+        Config::from_sources(toml.as_deref().map(|t| (file.as_str(), t)), env_vars, args)
+    }
+
+    ///The configuration from a file's name and content, environment-variables and program arguments.
+    /// Arguments are 'setting=value' - e.g. cache.ttl_minutes=120 - or one of the short-hands:
+    /// cache (or use_cache), ttl=, max_stale=, store= and cache_file=. A setting that does not exist is an error;
+    /// other arguments - e.g. --test-threads=1 - are left alone.
+    pub fn from_sources(
+        file: Option<(&str, &str)>,
+        env_vars: &[(String, String)],
+        args: &[String],
+    ) -> Result<Config, ConfigError> {
+        let mut value = match file {
+            Some((name, toml)) => toml.parse::<Value>().map_err(|e| ConfigError::Malformed {
+                origin: name.to_string(),
+                error: e.to_string(),
+            })?,
+            None => Value::Table(Default::default()),
+        };
+        let settings = known_settings();
+
+        for (name, v) in env_vars
+            .iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        {
+            match settings.keys().find(|s| env_var_name(s) == *name) {
+                Some(setting) => override_setting(&mut value, &settings, setting, v)?,
+                None if OTHER_ENV_VARS.contains(&name.as_str()) => {}
+                None => {
+                    return Err(ConfigError::UnknownSetting {
+                        origin: String::from("the environment"),
+                        setting: name.to_string(),
+                    })
+                }
+            }
+        }
+
+        for arg in args {
+            let (name, v) = match arg.split_once('=') {
+                Some((name, v)) => (name.trim(), v.trim()),
+                None if arg == "cache" || arg == "use_cache" => ("cache.enabled", "true"),
+                None => continue,
+            };
+            match name {
+                //Options like --test-threads=1 belong to someone else...
+                _ if name.starts_with('-') || name == "config" => {}
+                "ttl" => override_setting(&mut value, &settings, "cache.ttl_minutes", v)?,
+                "max_stale" => {
+                    override_setting(&mut value, &settings, "cache.max_stale_minutes", v)?
+                }
+                "store" => override_setting(&mut value, &settings, "cache.store", v)?,
+                "cache_file" => {
+                    override_setting(&mut value, &settings, "cache.file_path", v)?;
+                    override_setting(&mut value, &settings, "cache.sqlite_path", v)?;
+                }
+                _ if settings.contains_key(name) || name.starts_with("urls.") => {
+                    override_setting(&mut value, &settings, name, v)?
+                }
+                _ if name.contains('.') || name.contains('_') => {
+                    return Err(ConfigError::UnknownSetting {
+                        origin: String::from("the command-line"),
+                        setting: name.to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        let config: Config = value.try_into().map_err(|e| ConfigError::Malformed {
+            origin: file.map_or_else(
+                || String::from("the settings"),
+                |(name, _)| name.to_string(),
+            ),
+            error: e.to_string(),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    ///The urls - or the URL list - that make no sense; only checked when pages are to be fetched, see
+    /// [Config::validate].
+    pub fn validate_urls(&self) -> Result<(), ConfigError> {
+        if self.urls.is_empty() && !Path::new(&self.urls_file).is_file() {
+            return Err(ConfigError::InvalidValue {
+                setting: String::from("urls_file"),
+                value: self.urls_file.to_string(),
+                expected: String::from("an existing file holding the URL list"),
+            });
+        }
+        if self.urls.is_empty() {
+            url_list::read(&self.urls_file).map_err(ConfigError::InvalidUrlList)?;
+        }
+        match self
+            .urls
+            .iter()
+            .find(|(_, url)| url.parse::<http::Uri>().is_err())
+        {
+            Some((key, url)) => Err(ConfigError::InvalidValue {
+                setting: format!("urls.{}", key),
+                value: url.to_string(),
+                expected: String::from("a valid url"),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    ///The values that parse but make no sense; the urls are left to [Config::validate_urls].
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |setting: &str, value: &dyn fmt::Display, expected: &str| {
+            Err(ConfigError::InvalidValue {
+                setting: setting.to_string(),
+                value: value.to_string(),
+                expected: expected.to_string(),
+            })
+        };
+        if self.cache.ttl_minutes <= 0 {
+            return invalid(
+                "cache.ttl_minutes",
+                &self.cache.ttl_minutes,
+                "a positive number of minutes",
+            );
+        }
+        if self.cache.max_stale_minutes < 0 {
+            return invalid(
+                "cache.max_stale_minutes",
+                &self.cache.max_stale_minutes,
+                "zero or a positive number of minutes",
+            );
+        }
+        if self.history.max_age_days < 0 {
+            return invalid(
+                "history.max_age_days",
+                &self.history.max_age_days,
+                "zero or a positive number of days",
+            );
+        }
+        if self.cache.mongo.poll_seconds == 0 {
+            return invalid(
+                "cache.mongo.poll_seconds",
+                &0,
+                "a positive number of seconds",
+            );
+        }
+        if self.fetch.timeout_seconds == 0 {
+            return invalid("fetch.timeout_seconds", &0, "a positive number of seconds");
+        }
+        if !cfg!(feature = "sqlite") && self.cache.enabled && self.cache.store == StoreKind::Sqlite
+        {
+            return invalid(
+                "cache.store",
+                &"sqlite",
+                "file, mongo or memory; this build has no SQLite-support",
+            );
+        }
+        let cache_in_mongo = self.cache.enabled && self.cache.store == StoreKind::Mongo;
+        let history_in_mongo = self.history.enabled && self.history.store == HistoryKind::Mongo;
+        if !cfg!(feature = "mongo") && (cache_in_mongo || history_in_mongo) {
+            let (setting, expected) = if cache_in_mongo {
+                (
+                    "cache.store",
+                    "file, sqlite or memory; this build has no MongoDB-support",
+                )
+            } else {
+                ("history.store", "file; this build has no MongoDB-support")
+            };
+            return invalid(setting, &"mongo", expected);
+        }
+        if self.cache.store == StoreKind::Mongo || history_in_mongo {
+            let mongo = &self.cache.mongo;
+            for (setting, v) in [
+                ("cache.mongo.uri", &mongo.uri),
+                ("cache.mongo.database", &mongo.database),
+                ("cache.mongo.collection", &mongo.collection),
+            ]
+            .iter()
+            {
+                if v.trim().is_empty() {
+                    return invalid(setting, v, "a non-empty name");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///The urls to download the time-data from; keyed as in the URL list.
+    pub fn urls(&self) -> HashMap<String, String> {
+        if self.urls.is_empty() {
+            urls_from(&self.urls_file)
+        } else {
+            self.urls.clone().into_iter().collect()
+        }
+    }
+
+    ///The per-page Time To Live as set in the URL list.
+    pub fn page_ttls(&self) -> HashMap<String, Duration> {
+        if self.urls.is_empty() {
+            page_ttls_from(&self.urls_file)
+        } else {
+            HashMap::new()
+        }
+    }
+
+    ///The fetch-settings together with the per-page parser and locale as set in the URL list.
+    pub fn fetch_options(&self) -> FetchOptions {
+        let mut options = self.fetch.options();
+        if self.urls.is_empty() {
+            options.pages = page_options_from(&self.urls_file);
+        }
+        options
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::minutes(self.ttl_minutes)
+    }
+
+    pub fn max_staleness(&self) -> Duration {
+        Duration::minutes(self.max_stale_minutes)
+    }
+}
+
+impl MongoConfig {
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.poll_seconds)
+    }
+}
+
+impl HistoryConfig {
+    pub fn retention(&self) -> Retention {
+        Retention {
+            max_age: Some(self.max_age_days)
+                .filter(|days| *days > 0)
+                .map(Duration::days),
+            max_snapshots: Some(self.max_snapshots).filter(|max| *max > 0),
+        }
+    }
+}
+
+impl FetchConfig {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout_seconds)
+    }
+
+    ///Without the per-page options; see [Config::fetch_options].
+    pub fn options(&self) -> FetchOptions {
+        FetchOptions {
+            sort: self.sort,
+            timeout: self.timeout(),
+            ..Default::default()
+        }
+    }
+}
+
+///Every setting with its default value; e.g. 'cache.ttl_minutes' = 480.
+fn known_settings() -> BTreeMap<String, Value> {
+    let mut settings = BTreeMap::new();
+    flatten(
+        "",
+        &Value::try_from(Config::default()).expect("The default configuration is valid TOML"),
+        &mut settings,
+    );
+    settings
+}
+
+fn flatten(prefix: &str, value: &Value, settings: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Table(table) => {
+            for (k, v) in table {
+                flatten(&format!("{}{}.", prefix, k), v, settings);
+            }
+        }
+        _ => {
+            settings.insert(prefix.trim_end_matches('.').to_string(), value.clone());
+        }
+    }
+}
+
+fn env_var_name(setting: &str) -> String {
+    format!("{}{}", ENV_PREFIX, setting.replace('.', "_").to_uppercase())
+}
+
+///Sets the - dotted - setting; the value must be of the same type as the default.
+fn override_setting(
+    value: &mut Value,
+    settings: &BTreeMap<String, Value>,
+    setting: &str,
+    new_value: &str,
+) -> Result<(), ConfigError> {
+    let invalid = |expected: &str| ConfigError::InvalidValue {
+        setting: setting.to_string(),
+        value: new_value.to_string(),
+        expected: expected.to_string(),
+    };
+    //Only the urls have no default: they are strings...
+    let new_value = match settings.get(setting) {
+        Some(Value::Integer(_)) => {
+            Value::Integer(new_value.parse().map_err(|_| invalid("a whole number"))?)
+        }
+        Some(Value::Boolean(_)) => {
+            Value::Boolean(new_value.parse().map_err(|_| invalid("true or false"))?)
+        }
+        _ => Value::String(new_value.to_string()),
+    };
+
+    let mut path: Vec<&str> = setting.split('.').collect();
+    let name = path.pop().unwrap();
+    let mut table = value;
+    for part in path {
+        table = table
+            .as_table_mut()
+            .ok_or_else(|| invalid("a table"))?
+            .entry(part)
+            .or_insert_with(|| Value::Table(Default::default()));
+    }
+    table
+        .as_table_mut()
+        .ok_or_else(|| invalid("a table"))?
+        .insert(name.to_string(), new_value);
+    Ok(())
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable { file, error } => {
+                write!(
+                    f,
+                    "Could not read the configuration-file '{}': {}",
+                    file, error
+                )
+            }
+            ConfigError::Malformed { origin, error } => {
+                write!(f, "Invalid configuration in {}: {}", origin, error)
+            }
+            ConfigError::UnknownSetting { origin, setting } => write!(
+                f,
+                "Unknown setting '{}' in {}; known settings are: {}",
+                setting,
+                origin,
+                known_settings()
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ConfigError::InvalidUrlList(error) => write!(f, "Invalid URL list: {}", error),
+            ConfigError::InvalidValue {
+                setting,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value '{}' for setting '{}': expected {}",
+                value, setting, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 use std::process::Command as Cmd;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
 
 use chrono::{DateTime, FixedOffset, Utc};
 use druid::kurbo::Insets;
@@ -12,6 +14,7 @@ use druid::{AppDelegate, AppLauncher, Data, Env, Event, Lens, WindowDesc};
 
 use clock_widget::{Clock, ClockConfigData};
 
+use crate::cache_refresher::Snapshot;
 use crate::clock_widget;
 use crate::parse_timeanddate_dot_com::{CityData, TimeData};
 
@@ -39,16 +42,33 @@ fn set_city_count(new_value: usize) {
 pub fn show(time_data: HashMap<String, TimeData>, test_mode: bool) {
     //Show one-by-one the time-data-items...
     for (k, d) in time_data {
-        show_partition((k, &d), test_mode);
+        show_partition((k, &d), test_mode, None);
     }
 }
 
-fn show_partition((k, d): (String, &TimeData), test_mode: bool) {
+///Like [show] but the windows follow the updates - e.g. of a [crate::mongo_api::CacheWatcher] - : the cities a
+/// window shows are replaced by the updated cities of its page. Every window subscribes on its own.
+pub fn show_live(
+    time_data: HashMap<String, TimeData>,
+    test_mode: bool,
+    subscribe: &dyn Fn() -> Receiver<Snapshot>,
+) {
+    for (k, d) in time_data {
+        show_partition((k, &d), test_mode, Some(subscribe()));
+    }
+}
+
+fn show_partition(
+    (k, d): (String, &TimeData),
+    test_mode: bool,
+    updates: Option<Receiver<Snapshot>>,
+) {
     set_city_count(d.city_times.len());
     println!("Number of cities = {}", city_count());
     AppLauncher::with_window(WindowDesc::new(ui_builder).window_size((1500.0, 900.0)))
         .delegate(UiModel {
             test_mode,
+            updates: updates.map(|u| Arc::new(Mutex::new(u))),
             ..Default::default()
         })
         .launch((k, d).into())
@@ -61,7 +81,9 @@ fn ui_builder() -> impl Widget<UiModel> {
     let mut r = Flex::row();
 
     r.add_child(
-        Label::new(move |model: &UiModel, _: &Env| format!("City Times for {}", model.title)),
+        Label::new(move |model: &UiModel, _: &Env| {
+            format!("City Times for {}{}", model.title, model.stale_note)
+        }),
         1.0,
     );
     col.add_child(Padding::new(Insets::from((20.0, 20.0, 0.0, 50.0)), r), 1.0);
@@ -186,12 +208,14 @@ impl AppDelegate<UiModel> for UiModel {
     fn event(
         &mut self,
         event: Event,
-        _data: &mut UiModel,
+        data: &mut UiModel,
         _env: &Env,
         _ctx: &mut DelegateCtx,
     ) -> Option<Event> {
         match event {
             Event::AnimFrame(a) => println!("Anim Frame: {}", a),
+            //The clocks tick: a good moment to look for updates...
+            Event::Timer(_) => self.apply_updates(data),
             _ => {}
         }
         Some(event)
@@ -253,6 +277,8 @@ fn create_clock_config_data(model: &UiModel, row_num: usize) -> ClockConfigData
 struct UiModel {
     t: f64,
     title: String,
+    ///Tells the user the time-data could not be refreshed and how old it is.
+    stale_note: String,
     ///The Set of City-data
     cities: Vec<CityData>,
 
@@ -260,11 +286,26 @@ struct UiModel {
     row_num: usize,
     rendered_row: usize,
     test_mode: bool,
+    ///Newer time-data of all pages; only set for the delegate of a live window, see [show_live].
+    updates: Option<Arc<Mutex<Receiver<Snapshot>>>>,
 }
 
+///Druid only re-reads the labels of a model that is not the same; e.g. after [UiModel::apply_updates].
 impl Data for UiModel {
-    fn same(&self, _: &Self) -> bool {
-        true
+    fn same(&self, other: &Self) -> bool {
+        self.t.same(&other.t)
+            && self.title == other.title
+            && self.stale_note == other.stale_note
+            && self.row_num == other.row_num
+            && self.rendered_row == other.rendered_row
+            && self.test_mode == other.test_mode
+            && self.cities.len() == other.cities.len()
+            && self.cities.iter().zip(other.cities.iter()).all(|(c, o)| {
+                c.name == o.name
+                    && c.url == o.url
+                    && c.is_dls == o.is_dls
+                    && c.utc_offset.get() == o.utc_offset.get()
+            })
     }
 }
 
@@ -272,6 +313,7 @@ impl From<(String, &TimeData)> for UiModel {
     fn from((title, data): (String, &TimeData)) -> UiModel {
         UiModel {
             title: title,
+            stale_note: stale_note(data),
             row_num: data.city_times.len() / 10,
             cities: data.city_times.iter().cloned().collect(),
             ..Default::default()
@@ -279,7 +321,37 @@ impl From<(String, &TimeData)> for UiModel {
     }
 }
 
+fn stale_note(data: &TimeData) -> String {
+    if data.stale {
+        format!(
+            " - STALE: could not be refreshed; {} hours {} minutes old",
+            data.age().num_hours(),
+            data.age().num_minutes() % 60
+        )
+    } else {
+        String::new()
+    }
+}
+
 impl UiModel {
+    ///The window's rows are fixed once it is built: only the cities it shows already are updated.
+    fn apply_updates(&self, data: &mut UiModel) {
+        let updates = match &self.updates {
+            Some(updates) => updates,
+            None => return,
+        };
+        //Only the latest snapshot matters...
+        let latest = updates.lock().unwrap().try_iter().last();
+        if let Some(page) = latest.as_ref().and_then(|s| s.get(&data.title)) {
+            for city in data.cities.iter_mut() {
+                if let Some(updated) = page.city_times.iter().find(|c| c.name == city.name) {
+                    *city = updated.clone();
+                }
+            }
+            data.stale_note = stale_note(page);
+        }
+    }
+
     fn next(&mut self) {
         self.row_num = self.up_next();
     }
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::cache_store::Result;
+use crate::parse_timeanddate_dot_com::{rfc3339, TimeData};
+
+///Where every refresh is kept, so that one can see when a city's offset changed.
+pub trait HistoryStore: Send + Sync {
+    ///Adds a snapshot of these pages - keyed as in 'urls.txt' - taken at 'taken_at'.
+    fn append(&self, taken_at: DateTime<Utc>, pages: &HashMap<String, TimeData>) -> Result<()>;
+
+    ///The city - case-insensitive - in every snapshot, oldest first; on more pages means more points per snapshot.
+    fn timeline(&self, city: &str) -> Result<Vec<OffsetPoint>>;
+
+    ///Drops the snapshots the retention-policy no longer allows; returns the number of dropped snapshots.
+    fn prune(&self, retention: &Retention) -> Result<usize>;
+}
+
+///A city's offset as seen in one snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffsetPoint {
+    #[serde(with = "rfc3339")]
+    pub taken_at: DateTime<Utc>,
+    pub page: String,
+    pub name: String,
+    pub utc_offset_seconds: i32,
+    pub is_dls: bool,
+}
+
+///How long snapshots are kept; both limits apply when set.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Retention {
+    pub max_age: Option<Duration>,
+    ///The newest snapshots are kept.
+    pub max_snapshots: Option<usize>,
+}
+
+///One refresh; only what a timeline needs is kept of every city.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    #[serde(with = "rfc3339")]
+    taken_at: DateTime<Utc>,
+    cities: Vec<OffsetPoint>,
+}
+
+///Keeps the history in a file; one line of JSON per snapshot, so that appending is cheap.
+#[derive(Debug)]
+pub struct FileHistory {
+    path: PathBuf,
+    //Appending and pruning must not interleave...
+    lock: Mutex<()>,
+}
+
+impl OffsetPoint {
+    pub fn utc_offset(&self) -> FixedOffset {
+        FixedOffset::east(self.utc_offset_seconds)
+    }
+}
+
+///Only the points where the offset or DST-flag differs from the previous point of the same page.
+pub fn changes(timeline: &[OffsetPoint]) -> Vec<OffsetPoint> {
+    let mut previous: HashMap<&str, &OffsetPoint> = HashMap::new();
+    let mut changes = vec![];
+    for point in timeline {
+        let changed = previous.get(point.page.as_str()).map_or(true, |p| {
+            p.utc_offset_seconds != point.utc_offset_seconds || p.is_dls != point.is_dls
+        });
+        if changed {
+            changes.push(point.clone());
+        }
+        previous.insert(&point.page, point);
+    }
+    changes
+}
+
+///Every city of the pages as it is at 'taken_at'.
+pub fn offset_points(
+    taken_at: DateTime<Utc>,
+    pages: &HashMap<String, TimeData>,
+) -> Vec<OffsetPoint> {
+    pages
+        .iter()
+        .flat_map(|(key, td)| {
+            td.city_times.iter().map(move |city| OffsetPoint {
+                taken_at,
+                page: key.to_string(),
+                name: city.name.to_string(),
+                utc_offset_seconds: city.utc_offset.get().local_minus_utc(),
+                is_dls: city.is_dls,
+            })
+        })
+        .collect()
+}
+
+impl Retention {
+    ///The oldest moment a snapshot may have been taken at to be kept; if limited.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.max_age.map(|age| now - age)
+    }
+}
+
+impl FileHistory {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileHistory {
+        FileHistory {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> Result<Vec<Snapshot>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let mut snapshots = vec![];
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                snapshots.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(snapshots)
+    }
+}
+
+impl HistoryStore for FileHistory {
+    fn append(&self, taken_at: DateTime<Utc>, pages: &HashMap<String, TimeData>) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let snapshot = Snapshot {
+            taken_at,
+            cities: offset_points(taken_at, pages),
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&snapshot)?)?;
+        Ok(())
+    }
+
+    fn timeline(&self, city: &str) -> Result<Vec<OffsetPoint>> {
+        let _guard = self.lock.lock().unwrap();
+        let mut snapshots = self.read()?;
+        snapshots.sort_by_key(|s| s.taken_at);
+        Ok(snapshots
+            .into_iter()
+            .flat_map(|s| s.cities)
+            .filter(|p| p.name.eq_ignore_ascii_case(city.trim()))
+            .collect())
+    }
+
+    fn prune(&self, retention: &Retention) -> Result<usize> {
+        let _guard = self.lock.lock().unwrap();
+        let mut snapshots = self.read()?;
+        let count = snapshots.len();
+
+        snapshots.sort_by_key(|s| s.taken_at);
+        if let Some(cutoff) = retention.cutoff(Utc::now()) {
+            snapshots.retain(|s| s.taken_at >= cutoff);
+        }
+        if let Some(max) = retention.max_snapshots {
+            let excess = snapshots.len().saturating_sub(max);
+            snapshots.drain(..excess);
+        }
+        if snapshots.len() == count {
+            return Ok(0);
+        }
+
+        //Write to a temporary file first and then move it in place, so that a crash never loses the history...
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            for s in snapshots.iter() {
+                writeln!(writer, "{}", serde_json::to_string(s)?)?;
+            }
+            writer.flush()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(count - snapshots.len())
+    }
+}
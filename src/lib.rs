@@ -1,12 +1,31 @@
-#![warn(warnings)]
-#![macro_use]
+#[cfg(feature = "gui")]
 pub mod animation;
+#[cfg(feature = "config")]
 pub mod cache;
+#[cfg(feature = "archive")]
+pub mod cache_archive;
+pub mod cache_refresher;
+pub mod cache_store;
+pub mod city_query;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "gui")]
 pub mod clock_widget;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "gui")]
 pub mod druid_clock_app;
+#[cfg(feature = "gui")]
 pub mod druid_ui;
+pub mod history;
+#[cfg(feature = "mongo")]
 pub mod mongo_api;
 pub mod parse_timeanddate_dot_com;
+pub mod snapshot_diff;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub mod time_conversion;
+pub mod url_list;
 
 #[cfg(test)]
 mod tests;
@@ -14,8 +33,16 @@ mod tests;
 /// In order for this binary crate to run integration tests, both lib.rs AND main.rs have to be present.
 /// main::main() call this function.
 /// See more on this topic: https://doc.rust-lang.org/book/ch11-03-test-organization.html#integration-tests-for-binary-crates
+#[cfg(feature = "cli")]
 pub fn main(test_mode: bool) {
-    druid_ui::show(cache::fetch_time_data(), test_mode);
-    druid_clock_app::show(test_mode);
-    animation::show_animation(test_mode);
+    //The test-harness' arguments are no commands; the tests run the windows...
+    let args: Vec<String> = if test_mode {
+        vec![]
+    } else {
+        std::env::args().skip(1).collect()
+    };
+    let code = cli::run(&args, test_mode);
+    if code != cli::EXIT_OK {
+        std::process::exit(code);
+    }
 }
@@ -2,7 +2,11 @@
 /// ```
 /// cargo run use_cache ttl=480
 /// ```
-///
+/// Or one of the commands; e.g. list the cities in Europe observing DST...
+/// ```
+/// cargo run list --continent Europa --dst
+/// ```
+/// See 'cargo run help' for all commands.
 fn main() {
     rusttimeanddatedotcomparser::main(false);
 }
@@ -1,7 +1,5 @@
 //#![allow(warnings)]
-#![warn(warnings)]
 #![warn(rust_2018_idioms)]
-#![macro_use]
 
 use core::str::FromStr;
 use std::borrow::Borrow;
@@ -12,15 +10,23 @@ use std::rc::Rc;
 use std::str;
 use std::string::String;
 
+#[cfg(feature = "http-fetch")]
+use async_std::task;
 use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
 use http::Uri;
 use markup5ever::interface::Attribute;
 use markup5ever_rcdom::Node;
 use markup5ever_rcdom::NodeData;
 
-use local_data::UriWrapper;
-pub use local_data::{CityData, DayOfWeek, Sort, TimeData, UtcOffset};
-use reader::{fetch_url_body, get_dom, Result};
+pub use local_data::{
+    rfc3339, unix_epoch, CityData, ConflictPolicy, DayOfWeek, MergeConflict, Sort, TimeData,
+    UriWrapper, UtcOffset,
+};
+#[cfg(feature = "http-fetch")]
+use reader::fetch_url_body;
+use reader::{get_dom, Result};
+
+use crate::url_list::ParserKind;
 
 #[path = "web_page_reader.rs"]
 mod reader;
@@ -28,13 +34,60 @@ mod reader;
 #[path = "time_and_date_data.rs"]
 mod local_data;
 
-#[tokio::main]
-pub async fn fetch_time_data_from_website(url: String, sort: Sort) -> Result<TimeData> {
+///How pages are downloaded; see [crate::config::FetchConfig].
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    pub sort: Sort,
+    ///How long downloading one page may take.
+    pub timeout: std::time::Duration,
+    ///The options of single pages - keyed as in 'urls.txt' - as set in the URL list.
+    pub pages: HashMap<String, PageOptions>,
+}
+
+///How one page is downloaded and parsed; e.g. 'Europa=https://... locale=nl parser=worldclock'.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageOptions {
+    pub parser: ParserKind,
+    ///Asked for with the Accept-Language header; the site's default language otherwise.
+    pub locale: Option<String>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            sort: Sort::ByName,
+            timeout: std::time::Duration::from_secs(30),
+            pages: HashMap::new(),
+        }
+    }
+}
+
+///Downloads and parses one page; see [download_time_data] for the blocking way to download all pages.
+#[cfg(feature = "http-fetch")]
+pub async fn fetch_time_data_from_website(url: String, options: FetchOptions) -> Result<TimeData> {
+    fetch_page(&url, &options, &PageOptions::default()).await
+}
+
+#[cfg(feature = "http-fetch")]
+async fn fetch_page(url: &str, options: &FetchOptions, page: &PageOptions) -> Result<TimeData> {
     //Fetch the URL's DOM, Create the Time-Data and add UTC as city location;
     //Then let's get this party started, shall we...
+    let body = fetch_url_body(url, options.timeout, page.locale.as_deref()).await?;
+    match page.parser {
+        ParserKind::WorldClock => parse_page(url, body, Utc::now(), options.sort),
+    }
+}
+
+///Parses a world clock page - e.g. downloaded by an application itself - that was fetched at 'fetched_at'.
+pub fn parse_page(
+    url: &str,
+    body: String,
+    fetched_at: DateTime<Utc>,
+    sort: Sort,
+) -> Result<TimeData> {
     parse_node(
-        &get_dom(fetch_url_body(&url).await?).document.children,
-        add_utc(&mut create_time_data(&url), sort),
+        &get_dom(body).document.children,
+        add_utc(&mut create_time_data(url, fetched_at), sort),
         "",
         &mut CityData {
             sort,
@@ -112,7 +165,7 @@ fn process_element(c: &Rc<Node>, time_data: &mut TimeData, city_data: &mut CityD
                 city_data.time_string = get_children_as_flat_string(c);
 
                 //the time-string needs to be converted into an absolute date-time string; e.g. Thu, 02-01-2020 21:05 +13:00...
-                update_city_data(city_data);
+                update_city_data(city_data, &time_data.page_reference_time);
                 //Now that the city-data has been fully populated, it is to be stored...
                 time_data.city_times.insert(city_data.clone());
             } else {
@@ -134,17 +187,16 @@ fn get_children_as_flat_string(c: &Rc<Node>) -> String {
         .collect()
 }
 
-fn update_city_data(city_data: &mut CityData) {
+fn update_city_data(city_data: &mut CityData, utc_time: &DateTime<Utc>) {
     //the time string must at least have length 3 so that the day-of-the-week can be determined...
     if city_data.time_string.len() > 2 {
-        let utc_time: DateTime<Utc> = DateTime::from(Utc::now());
         let city_time_data = parse_city_time_string(&city_data.time_string);
-        city_data.utc_offset = city_utc_offset(&city_time_data, &utc_time);
+        city_data.utc_offset = city_utc_offset(&city_time_data, utc_time);
     }
 }
 
 ///The returned tuple has elements 1) DayOfWeek, 2) city-hour(24h format),3) city-minutes
-fn parse_city_time_string(time: &String) -> (DayOfWeek, i32, i32) {
+fn parse_city_time_string(time: &str) -> (DayOfWeek, i32, i32) {
     let mut tc = time.split_whitespace();
     let day_of_week = DayOfWeek::from(tc.next().unwrap());
 
@@ -199,7 +251,7 @@ pub fn city_utc_offset(
 
         if m < 0 {
             h -= 1;
-            m = m + 60;
+            m += 60;
         };
 
         UtcOffset::from(FixedOffset::east(3600 * h + 60 * m.abs()))
@@ -233,9 +285,9 @@ fn to_str(c: &NodeData, indent: &str) -> String {
                 .borrow()
                 .iter()
                 .fold(String::new(), |prev, attr| prev
-                    + &attr.name.local.get(..).unwrap()
+                    + attr.name.local.get(..).unwrap()
                     + "="
-                    + &attr.value.get(..).unwrap()
+                    + attr.value.get(..).unwrap()
                     + ", ")
                 .trim_end_matches(", ")
         ),
@@ -243,7 +295,7 @@ fn to_str(c: &NodeData, indent: &str) -> String {
     }
 }
 
-fn get_attribute<T: FromStr>(attr_name: &str, attrs: &Vec<Attribute>) -> Option<T> {
+fn get_attribute<T: FromStr>(attr_name: &str, attrs: &[Attribute]) -> Option<T> {
     for a in attrs.iter() {
         if a.name.local.get(..).unwrap() == attr_name {
             return a.value.get(..).unwrap().parse().ok();
@@ -252,7 +304,7 @@ fn get_attribute<T: FromStr>(attr_name: &str, attrs: &Vec<Attribute>) -> Option<
     None
 }
 
-fn get_city_id<T: FromStr>(attr_name: &str, attrs: &Vec<Attribute>) -> Option<T> {
+fn get_city_id<T: FromStr>(attr_name: &str, attrs: &[Attribute]) -> Option<T> {
     match get_attribute::<String>(attr_name, attrs) {
         Some(id) => id
             .trim_start_matches("p")
@@ -263,9 +315,12 @@ fn get_city_id<T: FromStr>(attr_name: &str, attrs: &Vec<Attribute>) -> Option<T>
     }
 }
 
-fn create_time_data(url: &String) -> TimeData {
+///The page has just been downloaded: its city-times refer to 'now'...
+fn create_time_data(url: &str, fetched_at: DateTime<Utc>) -> TimeData {
     TimeData {
         page_uri: UriWrapper::new(url.parse::<Uri>().unwrap()),
+        fetched_at,
+        page_reference_time: fetched_at,
         ..Default::default()
     }
 }
@@ -274,20 +329,49 @@ fn add_utc(data: &mut TimeData, sort: Sort) -> &mut TimeData {
     data.city_times.insert(CityData {
         name: "UTC".to_string(),
         url: String::from("https://www.timeanddate.com/time/aboututc.html"),
-        sort: sort,
+        sort,
         id: -1,
         ..Default::default()
     });
     data
 }
 
-pub fn download_time_data(sort: Sort, urls: &HashMap<String, String>) -> HashMap<String, TimeData> {
-    urls.iter()
-        .map(|(k, s)| {
-            (
-                k.to_string(),
-                fetch_time_data_from_website(s.to_string(), sort).unwrap(),
-            )
-        })
+///Blocks until all pages are downloaded; an async application awaits [fetch_pages] instead.
+#[cfg(feature = "http-fetch")]
+pub fn download_time_data(
+    options: FetchOptions,
+    urls: &HashMap<String, String>,
+) -> HashMap<String, TimeData> {
+    try_download_time_data(options, urls)
+        .into_iter()
+        .map(|(k, td)| (k, td.unwrap()))
         .collect()
 }
+
+///Same as [download_time_data] but a page that could not be downloaded yields its error instead of a panic.
+#[cfg(feature = "http-fetch")]
+pub fn try_download_time_data(
+    options: FetchOptions,
+    urls: &HashMap<String, String>,
+) -> HashMap<String, Result<TimeData>> {
+    task::block_on(fetch_pages(options, urls))
+}
+
+///Downloads the pages - keyed as in 'urls.txt' - one after another; a page that could not be downloaded
+/// yields its error.
+#[cfg(feature = "http-fetch")]
+pub async fn fetch_pages(
+    options: FetchOptions,
+    urls: &HashMap<String, String>,
+) -> HashMap<String, Result<TimeData>> {
+    let mut pages = HashMap::new();
+    let default_page = PageOptions::default();
+    for (k, s) in urls.iter() {
+        let page_options = options.pages.get(k).unwrap_or(&default_page);
+        let page = fetch_page(s, &options, page_options)
+            .await
+            .map(|td| td.with_source_page(k));
+        pages.insert(k.to_string(), page);
+    }
+    pages
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::Display;
+
+use crate::parse_timeanddate_dot_com::{CityData, TimeData, UtcOffset};
+
+///What changed between two snapshots of time-data; e.g. the cached data and the freshly downloaded data.
+/// Only pages - keyed as in 'urls.txt' - with at least one change are listed.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    pub pages: BTreeMap<String, PageDiff>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PageDiff {
+    pub added: Vec<CityData>,
+    pub removed: Vec<CityData>,
+    pub offset_changes: Vec<OffsetChange>,
+    pub dst_flips: Vec<DstFlip>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OffsetChange {
+    pub city: String,
+    pub old: UtcOffset,
+    pub new: UtcOffset,
+}
+
+#[derive(Debug, Clone)]
+pub struct DstFlip {
+    pub city: String,
+    pub was_dls: bool,
+    pub is_dls: bool,
+}
+
+///Compares the old snapshot with the new one page by page; a page that only exists in one of them
+/// has all its cities reported as added or removed.
+pub fn diff(old: &HashMap<String, TimeData>, new: &HashMap<String, TimeData>) -> SnapshotDiff {
+    let empty = TimeData::default();
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    SnapshotDiff {
+        pages: keys
+            .into_iter()
+            .map(|key| {
+                (
+                    key.to_string(),
+                    diff_page(
+                        old.get(key).unwrap_or(&empty),
+                        new.get(key).unwrap_or(&empty),
+                    ),
+                )
+            })
+            .filter(|(_, page_diff)| !page_diff.is_empty())
+            .collect(),
+    }
+}
+
+fn diff_page(old: &TimeData, new: &TimeData) -> PageDiff {
+    let old_cities = by_name(old);
+    let new_cities = by_name(new);
+    let mut page_diff = PageDiff::default();
+
+    for (name, old_city) in old_cities.iter() {
+        match new_cities.get(name) {
+            None => page_diff.removed.push((*old_city).clone()),
+            Some(new_city) => {
+                if old_city.utc_offset.get() != new_city.utc_offset.get() {
+                    page_diff.offset_changes.push(OffsetChange {
+                        city: name.to_string(),
+                        old: old_city.utc_offset.clone(),
+                        new: new_city.utc_offset.clone(),
+                    });
+                }
+                if old_city.is_dls != new_city.is_dls {
+                    page_diff.dst_flips.push(DstFlip {
+                        city: name.to_string(),
+                        was_dls: old_city.is_dls,
+                        is_dls: new_city.is_dls,
+                    });
+                }
+            }
+        }
+    }
+    page_diff.added = new_cities
+        .iter()
+        .filter(|(name, _)| !old_cities.contains_key(*name))
+        .map(|(_, city)| (*city).clone())
+        .collect();
+
+    page_diff
+}
+
+fn by_name(td: &TimeData) -> BTreeMap<&str, &CityData> {
+    td.city_times.iter().map(|c| (c.name.as_str(), c)).collect()
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
+
+impl PageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.offset_changes.is_empty()
+            && self.dst_flips.is_empty()
+    }
+}
+
+impl Display for SnapshotDiff {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(fmt, "No changes in the time-data.");
+        }
+        for (key, page_diff) in self.pages.iter() {
+            writeln!(fmt, "Changes in {}:", key)?;
+            for c in page_diff.added.iter() {
+                writeln!(fmt, "\t+ {} ({})", c.name, c.utc_offset.get())?;
+            }
+            for c in page_diff.removed.iter() {
+                writeln!(fmt, "\t- {} ({})", c.name, c.utc_offset.get())?;
+            }
+            for c in page_diff.offset_changes.iter() {
+                writeln!(
+                    fmt,
+                    "\t~ {}: offset {} -> {}",
+                    c.city,
+                    c.old.get(),
+                    c.new.get()
+                )?;
+            }
+            for c in page_diff.dst_flips.iter() {
+                writeln!(
+                    fmt,
+                    "\t~ {}: {}",
+                    c.city,
+                    if c.is_dls {
+                        "Winter Time -> DST"
+                    } else {
+                        "DST -> Winter Time"
+                    }
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
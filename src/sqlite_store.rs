@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::FixedOffset;
+use http::Uri;
+use rusqlite::{params, Connection, Row, Transaction, NO_PARAMS};
+
+use crate::cache_store::{stamped, stamped_all, CacheStore, Result};
+use crate::parse_timeanddate_dot_com::{rfc3339, CityData, Sort, TimeData, UriWrapper, UtcOffset};
+
+///Page-metadata and cities are stored in separate tables: one row per page, one row per city.
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS pages (
+        key                 TEXT PRIMARY KEY,
+        page_uri            TEXT NOT NULL,
+        last_updated        TEXT NOT NULL,
+        fetched_at          TEXT NOT NULL,
+        page_reference_time TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cities (
+        page_key           TEXT NOT NULL REFERENCES pages(key) ON DELETE CASCADE,
+        id                 INTEGER NOT NULL,
+        name               TEXT NOT NULL,
+        time_string        TEXT NOT NULL,
+        utc_offset_seconds INTEGER NOT NULL,
+        is_dls             INTEGER NOT NULL,
+        url                TEXT NOT NULL,
+        sort               TEXT NOT NULL,
+        source_page        TEXT
+    );
+    CREATE INDEX IF NOT EXISTS cities_page_key ON cities(page_key);
+    CREATE INDEX IF NOT EXISTS cities_name ON cities(name);
+    CREATE INDEX IF NOT EXISTS cities_utc_offset ON cities(utc_offset_seconds);
+";
+
+///A zero-daemon persistent cache: an embedded SQLite-database.
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<SqliteStore> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteStore {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    ///All cities with exactly this UTC-offset; served straight from the offset-index.
+    pub fn cities_with_offset(&self, offset: FixedOffset) -> Result<Vec<CityData>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT * FROM cities WHERE utc_offset_seconds = ?1 ORDER BY name, page_key",
+        )?;
+        let cities = statement
+            .query_map(params![offset.local_minus_utc()], to_city_data)?
+            .collect::<rusqlite::Result<Vec<CityData>>>()?;
+        Ok(cities)
+    }
+}
+
+impl CacheStore for SqliteStore {
+    fn load_all(&self) -> Result<HashMap<String, TimeData>> {
+        let connection = self.connection.lock().unwrap();
+        let mut pages = HashMap::new();
+
+        let mut statement = connection.prepare("SELECT * FROM pages")?;
+        let mut rows = statement.query(NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            let (key, td) = to_time_data(&connection, row)?;
+            pages.insert(key, td);
+        }
+        Ok(pages)
+    }
+
+    fn replace_all(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        self.restore_all(&stamped_all(data))
+    }
+
+    ///Everything happens in one transaction: readers never see a half-replaced cache.
+    fn restore_all(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        tx.execute("DELETE FROM pages", NO_PARAMS)?;
+        for (key, td) in data {
+            insert_page(&tx, key, td)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<TimeData>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT * FROM pages WHERE key = ?1")?;
+        let mut rows = statement.query(params![key])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(to_time_data(&connection, row)?.1)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&self, key: &str, data: &TimeData) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        //Deleting the page cascades to its cities...
+        tx.execute("DELETE FROM pages WHERE key = ?1", params![key])?;
+        insert_page(&tx, key, &stamped(data))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn put_many(&self, data: &HashMap<String, TimeData>) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        for (key, td) in data {
+            tx.execute("DELETE FROM pages WHERE key = ?1", params![key])?;
+            insert_page(&tx, key, &stamped(td))?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute("DELETE FROM pages WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+}
+
+fn insert_page(tx: &Transaction<'_>, key: &str, td: &TimeData) -> Result<()> {
+    tx.execute(
+        "INSERT INTO pages (key, page_uri, last_updated, fetched_at, page_reference_time)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            key,
+            td.page_uri().to_string(),
+            td.last_updated.to_rfc3339(),
+            td.fetched_at.to_rfc3339(),
+            td.page_reference_time.to_rfc3339(),
+        ],
+    )?;
+    let mut statement = tx.prepare(
+        "INSERT INTO cities (page_key, id, name, time_string, utc_offset_seconds, is_dls, url, sort, source_page)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for c in td.city_times.iter() {
+        statement.execute(params![
+            key,
+            c.id,
+            c.name,
+            c.time_string,
+            c.utc_offset.get().local_minus_utc(),
+            c.is_dls,
+            c.url,
+            format!("{:?}", c.sort),
+            c.source_page,
+        ])?;
+    }
+    Ok(())
+}
+
+fn to_time_data(connection: &Connection, row: &Row<'_>) -> Result<(String, TimeData)> {
+    let key: String = row.get("key")?;
+    let page_uri: String = row.get("page_uri")?;
+
+    let mut statement = connection.prepare("SELECT * FROM cities WHERE page_key = ?1")?;
+    let city_times = statement
+        .query_map(params![key], to_city_data)?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let td = TimeData {
+        page_uri: UriWrapper::new(page_uri.parse::<Uri>()?),
+        last_updated: rfc3339::parse(&row.get::<_, String>("last_updated")?),
+        fetched_at: rfc3339::parse(&row.get::<_, String>("fetched_at")?),
+        page_reference_time: rfc3339::parse(&row.get::<_, String>("page_reference_time")?),
+        city_times,
+        ..Default::default()
+    };
+    Ok((key, td))
+}
+
+fn to_city_data(row: &Row<'_>) -> rusqlite::Result<CityData> {
+    Ok(CityData {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        time_string: row.get("time_string")?,
+        utc_offset: UtcOffset::from(FixedOffset::east(row.get("utc_offset_seconds")?)),
+        is_dls: row.get("is_dls")?,
+        url: row.get("url")?,
+        sort: match row.get::<_, String>("sort")?.as_str() {
+            "ByOffset" => Sort::ByOffset,
+            _ => Sort::ByName,
+        },
+        source_page: row.get("source_page")?,
+    })
+}
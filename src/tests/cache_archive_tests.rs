@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use chrono::{TimeZone, Utc};
+
+use crate::cache_archive::{export, import, read, ArchiveError, ARCHIVE_VERSION};
+use crate::cache_store::{CacheStore, MemoryStore};
+use crate::parse_timeanddate_dot_com::{CityData, TimeData};
+use crate::sqlite_store::SqliteStore;
+
+fn page(city: &str) -> TimeData {
+    let mut td = TimeData::default();
+    td.city_times.insert(CityData {
+        name: city.to_string(),
+        ..Default::default()
+    });
+    td
+}
+
+fn warmed_cache() -> MemoryStore {
+    let store = MemoryStore::new();
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    pages.insert(String::from("Asia"), page("Tokyo"));
+    store.replace_all(&pages).unwrap();
+    store
+}
+
+fn urls(keys: &[&str]) -> HashMap<String, String> {
+    keys.iter()
+        .map(|k| {
+            (
+                k.to_string(),
+                String::from("https://www.timeanddate.com/worldclock/"),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn export_import_test() {
+    for name in ["cache_archive_test.json", "cache_archive_test.json.gz"].iter() {
+        let file = env::temp_dir().join(name);
+        assert_eq!(export(&warmed_cache(), &file).unwrap(), 2);
+        assert_eq!(read(&file).unwrap().version, ARCHIVE_VERSION);
+
+        //Any backend will do...
+        let sqlite = SqliteStore::open_in_memory().unwrap();
+        assert_eq!(
+            import(&sqlite, &file, &urls(&["Europa", "Asia", "Afrika"])).unwrap(),
+            2
+        );
+        let imported = sqlite.load_all().unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(
+            imported["Asia"].city_times.iter().next().unwrap().name,
+            "Tokyo"
+        );
+        fs::remove_file(&file).unwrap();
+    }
+}
+
+#[test]
+fn import_keeps_the_age_test() {
+    //An imported page is as old as it was when exported; it does not count as freshly downloaded...
+    let exported_at = Utc.ymd(2020, 8, 21).and_hms(13, 30, 0);
+    let mut pages = HashMap::new();
+    pages.insert(
+        String::from("Europa"),
+        TimeData {
+            last_updated: exported_at,
+            ..page("Berlin")
+        },
+    );
+    let file = env::temp_dir().join("cache_archive_age_test.json");
+    export(&MemoryStore::with_pages(pages), &file).unwrap();
+
+    let sqlite = SqliteStore::open_in_memory().unwrap();
+    import(&sqlite, &file, &urls(&["Europa"])).unwrap();
+    assert_eq!(
+        sqlite.load_all().unwrap()["Europa"].last_updated,
+        exported_at
+    );
+
+    let memory = MemoryStore::new();
+    import(&memory, &file, &urls(&["Europa"])).unwrap();
+    assert_eq!(
+        memory.load_all().unwrap()["Europa"].last_updated,
+        exported_at
+    );
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn import_validation_test() {
+    let file = env::temp_dir().join("cache_archive_validation_test.json");
+    export(&warmed_cache(), &file).unwrap();
+
+    //Asia is not in the urls: nothing is imported...
+    let store = MemoryStore::new();
+    let error = import(&store, &file, &urls(&["Europa"])).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<ArchiveError>(),
+        Some(&ArchiveError::UnknownPages(vec![String::from("Asia")]))
+    );
+    assert!(store.load_all().unwrap().is_empty());
+
+    //An archive of another version is refused...
+    let content = fs::read_to_string(&file).unwrap().replacen(
+        &format!("\"version\": {}", ARCHIVE_VERSION),
+        "\"version\": 99",
+        1,
+    );
+    fs::write(&file, content).unwrap();
+    assert_eq!(
+        import(&store, &file, &urls(&["Europa", "Asia"]))
+            .unwrap_err()
+            .to_string(),
+        "Unsupported cache-archive version 99; only version 1 can be imported"
+    );
+    fs::remove_file(&file).unwrap();
+}
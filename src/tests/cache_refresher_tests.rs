@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::time;
+
+use chrono::{Duration, NaiveTime, TimeZone, Utc};
+
+use crate::cache_refresher::{CacheRefresher, Schedule};
+use crate::cache_store::{CacheStore, MemoryStore};
+use crate::config::Config;
+use crate::parse_timeanddate_dot_com::{CityData, TimeData};
+
+fn page(city: &str) -> TimeData {
+    let mut td = TimeData::default();
+    td.city_times.insert(CityData {
+        name: city.to_string(),
+        ..Default::default()
+    });
+    td
+}
+
+#[test]
+fn schedule_test() {
+    let instant = Utc.ymd(2020, 8, 21).and_hms(13, 30, 0);
+
+    assert_eq!(
+        Schedule::Every(Duration::minutes(15)).next_after(&instant),
+        Utc.ymd(2020, 8, 21).and_hms(13, 45, 0)
+    );
+
+    let twice_a_day = Schedule::DailyAt(vec![
+        NaiveTime::from_hms(2, 0, 0),
+        NaiveTime::from_hms(14, 0, 0),
+    ]);
+    assert_eq!(
+        twice_a_day.next_after(&instant),
+        Utc.ymd(2020, 8, 21).and_hms(14, 0, 0)
+    );
+    //Past the last time of the day: the first time tomorrow...
+    assert_eq!(
+        twice_a_day.next_after(&Utc.ymd(2020, 8, 21).and_hms(14, 0, 0)),
+        Utc.ymd(2020, 8, 22).and_hms(2, 0, 0)
+    );
+}
+
+#[test]
+fn refresher_publishes_and_stops_test() {
+    //A fresh cache: nothing gets downloaded, the cached data is published...
+    let store = MemoryStore::new();
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    store.replace_all(&pages).unwrap();
+
+    let mut urls = BTreeMap::new();
+    urls.insert(
+        String::from("Europa"),
+        String::from("https://www.timeanddate.com/worldclock/?continent=europe"),
+    );
+    let refresher = CacheRefresher::start(
+        Config {
+            urls,
+            ..Default::default()
+        },
+        Box::new(store),
+        Schedule::Every(Duration::hours(1)),
+    );
+    let updates = refresher.subscribe();
+    let snapshot = updates.recv_timeout(time::Duration::from_secs(10)).unwrap();
+    assert_eq!(snapshot.keys().collect::<Vec<_>>(), vec!["Europa"]);
+
+    //Stopping does not wait for the next scheduled refresh; the channel is closed afterwards...
+    let before = time::Instant::now();
+    refresher.stop();
+    assert!(before.elapsed() < time::Duration::from_secs(10));
+    assert!(updates.recv().is_err());
+}
+
+#[test]
+fn refresher_follows_updates_test() {
+    let store = MemoryStore::new();
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    store.replace_all(&pages).unwrap();
+
+    let mut urls = BTreeMap::new();
+    urls.insert(String::from("Europa"), String::from("http://localhost:1/"));
+    let refresher = CacheRefresher::start(
+        Config {
+            urls,
+            ..Default::default()
+        },
+        Box::new(store),
+        Schedule::Every(Duration::hours(1)),
+    );
+    let updates = refresher.subscribe();
+    updates.recv_timeout(time::Duration::from_secs(10)).unwrap();
+
+    //E.g. a watcher of a cache shared with other machines...
+    let (others, followed) = channel();
+    refresher.follow(followed);
+
+    //An older page is no news; a newer one is...
+    let mut older = HashMap::new();
+    older.insert(
+        String::from("Europa"),
+        TimeData {
+            last_updated: Utc::now() - Duration::hours(1),
+            ..page("Paris")
+        },
+    );
+    others.send(Arc::new(older)).unwrap();
+    let mut newer = HashMap::new();
+    newer.insert(
+        String::from("Europa"),
+        TimeData {
+            last_updated: Utc::now() + Duration::minutes(1),
+            ..page("Madrid")
+        },
+    );
+    others.send(Arc::new(newer)).unwrap();
+
+    let snapshot = updates.recv_timeout(time::Duration::from_secs(10)).unwrap();
+    assert_eq!(
+        snapshot["Europa"].city_times.iter().next().unwrap().name,
+        "Madrid"
+    );
+    refresher.stop();
+}
+
+#[test]
+fn refresher_publishes_stale_data_test() {
+    //The page has expired and cannot be refreshed: it is published marked stale...
+    let mut pages = HashMap::new();
+    pages.insert(
+        String::from("Europa"),
+        TimeData {
+            last_updated: Utc::now() - Duration::hours(9),
+            fetched_at: Utc::now() - Duration::hours(9),
+            ..page("Berlin")
+        },
+    );
+    let mut urls = BTreeMap::new();
+    urls.insert(String::from("Europa"), String::from("http://localhost:1/"));
+
+    let refresher = CacheRefresher::start(
+        Config {
+            urls,
+            ..Default::default()
+        },
+        Box::new(MemoryStore::with_pages(pages)),
+        Schedule::Every(Duration::hours(1)),
+    );
+    let snapshot = refresher
+        .subscribe()
+        .recv_timeout(time::Duration::from_secs(30))
+        .unwrap();
+    assert!(snapshot["Europa"].stale);
+}
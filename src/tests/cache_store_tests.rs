@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+#[cfg(all(feature = "mongo", feature = "http-fetch"))]
+use async_std::task;
+#[cfg(feature = "sqlite")]
+use chrono::FixedOffset;
+use chrono::{Duration, Utc};
+
+#[cfg(feature = "http-fetch")]
+use crate::cache::use_cache;
+use crate::cache_store::{CacheStore, FileStore, MemoryStore};
+#[cfg(feature = "http-fetch")]
+use crate::config::Config;
+#[cfg(all(feature = "mongo", feature = "http-fetch"))]
+use crate::history::Retention;
+#[cfg(feature = "mongo")]
+use crate::mongo_api::MongoStore;
+#[cfg(feature = "sqlite")]
+use crate::parse_timeanddate_dot_com::UtcOffset;
+#[cfg(all(feature = "mongo", feature = "http-fetch"))]
+use crate::parse_timeanddate_dot_com::{fetch_pages, FetchOptions};
+use crate::parse_timeanddate_dot_com::{CityData, TimeData};
+#[cfg(feature = "sqlite")]
+use crate::sqlite_store::SqliteStore;
+
+fn page(city: &str) -> TimeData {
+    let mut td = TimeData::default();
+    td.city_times.insert(CityData {
+        name: city.to_string(),
+        ..Default::default()
+    });
+    td
+}
+
+///Every store must behave the same...
+fn exercise(store: &dyn CacheStore) {
+    assert!(store.load_all().unwrap().is_empty());
+    assert!(store.get("Europa").unwrap().is_none());
+
+    let before = Utc::now() - Duration::seconds(1);
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    pages.insert(String::from("Asia"), page("Tokyo"));
+    store.replace_all(&pages).unwrap();
+
+    let loaded = store.load_all().unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert!(loaded["Europa"].last_updated > before);
+    assert_eq!(
+        loaded["Asia"].city_times.iter().next().unwrap().name,
+        "Tokyo"
+    );
+
+    store.put("Afrika", &page("Cairo")).unwrap();
+    assert_eq!(
+        store
+            .get("Afrika")
+            .unwrap()
+            .unwrap()
+            .city_times
+            .iter()
+            .next()
+            .unwrap()
+            .name,
+        "Cairo"
+    );
+    assert_eq!(store.load_all().unwrap().len(), 3);
+
+    store.remove("Afrika").unwrap();
+    assert!(store.get("Afrika").unwrap().is_none());
+    store.remove("Atlantis").unwrap();
+
+    //Putting many pages replaces the ones with the same key only...
+    let mut many = HashMap::new();
+    many.insert(String::from("Asia"), page("Seoul"));
+    many.insert(String::from("Afrika"), page("Lagos"));
+    store.put_many(&many).unwrap();
+    let loaded = store.load_all().unwrap();
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(loaded["Asia"].city_times.len(), 1);
+    assert_eq!(
+        loaded["Asia"].city_times.iter().next().unwrap().name,
+        "Seoul"
+    );
+    assert_eq!(
+        loaded["Europa"].city_times.iter().next().unwrap().name,
+        "Berlin"
+    );
+    store.remove("Afrika").unwrap();
+
+    //Replacing drops pages that are no longer wanted...
+    pages.remove("Asia");
+    store.replace_all(&pages).unwrap();
+    let mut keys: Vec<String> = store.load_all().unwrap().keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["Europa"]);
+}
+
+#[test]
+fn memory_store_test() {
+    exercise(&MemoryStore::new());
+}
+
+#[test]
+fn file_store_test() {
+    let path = env::temp_dir().join(format!("time_data_cache_test_{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    exercise(&FileStore::new(&path));
+
+    //A fresh store on the same file sees the same data...
+    assert_eq!(FileStore::new(&path).load_all().unwrap().len(), 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn sqlite_store_test() {
+    let store = SqliteStore::open_in_memory().unwrap();
+    exercise(&store);
+
+    //The city-rows are indexed by offset...
+    let mut td = page("Kolkata");
+    td.city_times.insert(CityData {
+        name: String::from("Colombo"),
+        utc_offset: UtcOffset::from(FixedOffset::east(5 * 3600 + 1800)),
+        is_dls: false,
+        ..Default::default()
+    });
+    store.put("Asia", &td).unwrap();
+    assert_eq!(
+        store
+            .cities_with_offset(FixedOffset::east(5 * 3600 + 1800))
+            .unwrap()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Colombo"]
+    );
+    assert_eq!(store.get("Asia").unwrap().unwrap().city_times.len(), 2);
+}
+
+#[test]
+#[cfg(feature = "http-fetch")]
+fn use_cache_serves_valid_cache_test() {
+    //A complete and fresh cache is served without downloading anything...
+    let store = MemoryStore::new();
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    store.replace_all(&pages).unwrap();
+
+    let mut urls = HashMap::new();
+    urls.insert(
+        String::from("Europa"),
+        String::from("https://www.timeanddate.com/worldclock/?continent=europe"),
+    );
+    assert_eq!(
+        use_cache(&Config::default(), &store, &urls, &HashMap::new())
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+#[cfg(feature = "http-fetch")]
+fn use_cache_prunes_unwanted_pages_test() {
+    //Only pruning is needed: nothing gets downloaded and the fresh page is left untouched...
+    let store = MemoryStore::new();
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    pages.insert(String::from("Asia"), page("Tokyo"));
+    store.replace_all(&pages).unwrap();
+    let stored_at = store.get("Europa").unwrap().unwrap().last_updated;
+
+    let mut urls = HashMap::new();
+    urls.insert(
+        String::from("Europa"),
+        String::from("https://www.timeanddate.com/worldclock/?continent=europe"),
+    );
+    let map = use_cache(&Config::default(), &store, &urls, &HashMap::new()).unwrap();
+    assert_eq!(map.keys().collect::<Vec<_>>(), vec!["Europa"]);
+    assert_eq!(map["Europa"].last_updated, stored_at);
+}
+
+#[test]
+#[cfg(feature = "http-fetch")]
+fn use_cache_serves_stale_if_error_test() {
+    //The page has expired and the refresh fails: the cached page is served, marked stale...
+    let mut pages = HashMap::new();
+    pages.insert(
+        String::from("Europa"),
+        TimeData {
+            last_updated: Utc::now() - Duration::hours(9),
+            fetched_at: Utc::now() - Duration::hours(9),
+            ..page("Berlin")
+        },
+    );
+    let store = MemoryStore::with_pages(pages);
+
+    let mut urls = HashMap::new();
+    urls.insert(String::from("Europa"), String::from("http://localhost:1/"));
+    let map = use_cache(&Config::default(), &store, &urls, &HashMap::new()).unwrap();
+    assert!(map["Europa"].stale);
+    assert!(map["Europa"].age() >= Duration::hours(9));
+
+    //The stale page is left in the store as it was, so that the next run tries again...
+    assert!(!store.get("Europa").unwrap().unwrap().stale);
+    assert!(store.get("Europa").unwrap().unwrap().last_updated < Utc::now() - Duration::hours(8));
+}
+
+#[test]
+#[cfg(feature = "http-fetch")]
+fn use_cache_fails_without_usable_data_test() {
+    //Nothing cached and nothing downloadable: an error, not a panic...
+    let mut urls = HashMap::new();
+    urls.insert(String::from("Europa"), String::from("http://localhost:1/"));
+    let error = use_cache(
+        &Config::default(),
+        &MemoryStore::new(),
+        &urls,
+        &HashMap::new(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Could not download any time-data and there is no usable cached time-data"
+    );
+}
+
+#[test]
+#[cfg(feature = "mongo")]
+fn mongo_store_connection_string_test() {
+    //Parsing the connection string needs no mongod...
+    assert!(MongoStore::connect(
+        "mongodb://localhost:27017/?replicaSet=rs0",
+        "test",
+        "city_data"
+    )
+    .is_ok());
+    assert!(MongoStore::connect("http://localhost:27017", "test", "city_data").is_err());
+}
+
+#[test]
+#[cfg(all(feature = "mongo", feature = "http-fetch"))]
+fn async_api_test() {
+    //Async services spawn these futures onto their own executors: they must be Send...
+    fn assert_send<T: Send>(_: &T) {}
+    let store = task::block_on(MongoStore::connect_async(
+        "mongodb://localhost:27017",
+        "test",
+        "city_data",
+    ))
+    .unwrap();
+    let urls = HashMap::new();
+    assert_send(&store.load_all_async());
+    assert_send(&store.put_async("Europa", &TimeData::default()));
+    assert_send(&store.append_async(Utc::now(), &HashMap::new()));
+    assert_send(&store.timeline_async("Berlin"));
+    assert_send(&store.prune_async(&Retention::default()));
+    assert_send(&fetch_pages(FetchOptions::default(), &urls));
+
+    //...and they run within an async application without a runtime of their own.
+    assert!(task::block_on(async { fetch_pages(FetchOptions::default(), &urls).await }).is_empty());
+}
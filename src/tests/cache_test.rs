@@ -2,8 +2,13 @@ use std::collections::{BTreeSet, HashMap};
 
 use chrono::{Duration, Utc};
 
-use crate::cache::{cache_invalid, urls_from};
-use crate::parse_timeanddate_dot_com::TimeData;
+use crate::cache::{cache_invalid, page_ttls_from, page_validity, urls_from, PageValidity};
+use crate::parse_timeanddate_dot_com::{CityData, TimeData};
+
+//The default cache.ttl_minutes...
+fn ttl() -> Duration {
+    Duration::minutes(480)
+}
 
 #[test]
 fn cache_invalid_test() {
@@ -11,70 +16,69 @@ fn cache_invalid_test() {
     let data = &mut HashMap::default();
     let urls = &mut HashMap::default();
 
-    assert_eq!(cache_invalid(data, urls), true);
+    assert!(cache_invalid(data, urls, ttl()));
 
     //1 cache; 0 urls: unequal count...
     data.insert(
         String::from("Key1"),
         TimeData {
-            last_updated: Utc::now().to_rfc3339(),
+            last_updated: Utc::now(),
             ..Default::default()
         },
     );
-    assert_eq!(cache_invalid(data, urls), true);
+    assert!(cache_invalid(data, urls, ttl()));
 
     //1 cache; 1 urls: equal count, but url-key not found in cache...
     urls.insert(String::from("Key2"), String::from("Url1"));
-    assert_eq!(cache_invalid(data, urls), true);
+    assert!(cache_invalid(data, urls, ttl()));
 
     //1 cache; 2 urls: unequal equal count, but url-key IS found in cache...
     urls.insert(String::from("Key1"), String::from("Url1"));
-    assert_eq!(cache_invalid(data, urls), true);
+    assert!(cache_invalid(data, urls, ttl()));
 
     //2 cache; 2 urls: equal equal count, AND url-keys ARE all found in cache...
     data.insert(
         String::from("Key2"),
         TimeData {
-            last_updated: Utc::now().to_rfc3339(),
+            last_updated: Utc::now(),
             ..Default::default()
         },
     );
-    assert_eq!(cache_invalid(data, urls), false);
+    assert!(!cache_invalid(data, urls, ttl()));
 
     //3 cache; 2 urls: unequal equal count, AND url-keys ARE all found in cache...
     data.insert(
         String::from("Key3"),
         TimeData {
-            last_updated: Utc::now().to_rfc3339(),
+            last_updated: Utc::now(),
             ..Default::default()
         },
     );
-    assert_eq!(cache_invalid(data, urls), true);
+    assert!(cache_invalid(data, urls, ttl()));
 
     //3 cache; 3 urls: equal equal count, AND url-keys ARE all found in cache...
     urls.insert(String::from("Key3"), String::from("Url1"));
-    assert_eq!(cache_invalid(data, urls), false);
+    assert!(!cache_invalid(data, urls, ttl()));
 
     //Invalidate cache by making a the last_updated field in a the cached item older than 480 minutes ago
     data.insert(
         String::from("Key3"),
         TimeData {
-            last_updated: (Utc::now() - Duration::minutes(8 * 60 + 1)).to_rfc3339(),
+            last_updated: Utc::now() - Duration::minutes(8 * 60 + 1),
             ..Default::default()
         },
     );
-    assert_eq!(cache_invalid(data, urls), true);
+    assert!(cache_invalid(data, urls, ttl()));
 
     //Validate cache by making a the last_updated field in a the cached item one second less than 480 minutes ago
     data.insert(
         String::from("Key3"),
         TimeData {
-            last_updated: (Utc::now() - Duration::minutes(8 * 60) + Duration::seconds(1))
-                .to_rfc3339(),
+            last_updated: Utc::now() - Duration::minutes(8 * 60) + Duration::seconds(1),
             ..Default::default()
         },
     );
-    assert_eq!(cache_invalid(data, urls), false);
+    assert!(!cache_invalid(data, urls, ttl()));
 }
 
 #[test]
@@ -103,3 +107,125 @@ fn some_urls_commented_out_test() {
         "Afrika=A|Asia=A|Europa=E|North Americas=N|South Americas=S|".to_string()
     );
 }
+
+#[test]
+fn default_time_data_is_expired_test() {
+    //A default TimeData used to hold an empty 'last_updated'-string which panicked the cache-validation...
+    let mut data = HashMap::default();
+    let mut urls = HashMap::default();
+    data.insert(String::from("Key1"), TimeData::default());
+    urls.insert(String::from("Key1"), String::from("Url1"));
+
+    assert!(cache_invalid(&data, &urls, ttl()));
+}
+
+#[test]
+fn page_validity_test() {
+    let mut data = HashMap::default();
+    let mut urls = HashMap::default();
+    let fresh = TimeData {
+        last_updated: Utc::now(),
+        ..Default::default()
+    };
+    let expired = TimeData {
+        last_updated: Utc::now() - Duration::minutes(8 * 60 + 1),
+        ..Default::default()
+    };
+    data.insert(String::from("Fresh"), fresh);
+    data.insert(String::from("Expired"), expired);
+    data.insert(String::from("Unwanted"), TimeData::default());
+    urls.insert(String::from("Fresh"), String::from("Url1"));
+    urls.insert(String::from("Expired"), String::from("Url2"));
+    urls.insert(String::from("Missing"), String::from("Url3"));
+
+    let validity = page_validity(&data, &urls, &HashMap::new(), ttl());
+    assert_eq!(
+        validity.into_iter().collect::<Vec<_>>(),
+        vec![
+            (String::from("Expired"), PageValidity::Expired),
+            (String::from("Fresh"), PageValidity::Fresh),
+            (String::from("Missing"), PageValidity::Missing),
+            (String::from("Unwanted"), PageValidity::Unwanted),
+        ]
+    );
+}
+
+#[test]
+fn page_ttls_from_test() {
+    //The options do not end up in the urls...
+    assert_eq!(
+        urls_from("./src/tests/test-urls-with-ttl.txt")
+            .iter()
+            .map(|(k, v)| format!("{}={}|", k, v))
+            .collect::<BTreeSet<String>>()
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<String>(),
+        "Afrika=A|Europa=E|Popular Cities=P|".to_string()
+    );
+
+    let ttls = page_ttls_from("./src/tests/test-urls-with-ttl.txt");
+    assert_eq!(ttls.len(), 2);
+    assert_eq!(ttls["Popular Cities"], Duration::minutes(60));
+    assert_eq!(ttls["Europa"], Duration::minutes(120));
+}
+
+#[test]
+fn per_page_ttl_test() {
+    let mut data = HashMap::default();
+    let mut urls = HashMap::default();
+    let mut ttls = HashMap::default();
+    let an_hour_old = TimeData {
+        last_updated: Utc::now() - Duration::minutes(61),
+        ..Default::default()
+    };
+    data.insert(String::from("Short"), an_hour_old.clone());
+    data.insert(String::from("Default"), an_hour_old);
+    urls.insert(String::from("Short"), String::from("Url1"));
+    urls.insert(String::from("Default"), String::from("Url2"));
+    ttls.insert(String::from("Short"), Duration::minutes(60));
+
+    let validity = page_validity(&data, &urls, &ttls, ttl());
+    assert_eq!(validity["Short"], PageValidity::Expired);
+    assert_eq!(validity["Default"], PageValidity::Fresh);
+}
+
+#[test]
+fn dst_transition_expires_page_test() {
+    let mut data = HashMap::default();
+    let mut urls = HashMap::default();
+    let mut ttls = HashMap::default();
+    let city = |name: &str| CityData {
+        name: String::from(name),
+        ..Default::default()
+    };
+    //More than a year old: Berlin has certainly switched between CET and CEST since...
+    let a_year_ago = Utc::now() - Duration::days(400);
+    data.insert(
+        String::from("Berlin"),
+        TimeData {
+            last_updated: a_year_ago,
+            city_times: vec![city("Berlin")].into_iter().collect(),
+            ..Default::default()
+        },
+    );
+    //...whereas UTC never does, nor does a city without an IANA-zone.
+    data.insert(
+        String::from("Steady"),
+        TimeData {
+            last_updated: a_year_ago,
+            city_times: vec![city("UTC"), city("Nowhere Special")]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        },
+    );
+    for key in ["Berlin", "Steady"].iter() {
+        urls.insert(key.to_string(), String::from("Url"));
+        ttls.insert(key.to_string(), Duration::days(1000));
+    }
+
+    let validity = page_validity(&data, &urls, &ttls, ttl());
+    assert_eq!(validity["Berlin"], PageValidity::Expired);
+    assert_eq!(validity["Steady"], PageValidity::Fresh);
+}
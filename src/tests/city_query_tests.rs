@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use chrono::{FixedOffset, NaiveTime, TimeZone, Utc};
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::city_query::CityQuery;
+use crate::parse_timeanddate_dot_com::{CityData, ConflictPolicy, Sort, TimeData, UtcOffset};
+
+fn city(name: &str, country: &str, offset_minutes: i32, is_dls: bool) -> CityData {
+    CityData {
+        name: name.to_string(),
+        url: format!(
+            "https://www.timeanddate.com/worldclock/{}/{}",
+            country,
+            name.to_lowercase()
+        ),
+        utc_offset: UtcOffset::from(FixedOffset::east(offset_minutes * 60)),
+        is_dls,
+        ..Default::default()
+    }
+}
+
+fn pages() -> HashMap<String, TimeData> {
+    let mut europe = TimeData::default();
+    europe
+        .city_times
+        .insert(city("Berlin", "germany", 120, true));
+    europe.city_times.insert(city("London", "uk", 60, true));
+    europe
+        .city_times
+        .insert(city("Reykjavik", "iceland", 0, false));
+
+    let mut asia = TimeData::default();
+    asia.city_times.insert(city("Kolkata", "india", 330, false));
+    asia.city_times
+        .insert(city("Kathmandu", "nepal", 345, false));
+    asia.city_times.insert(city("Tokyo", "japan", 540, false));
+
+    let mut popular = TimeData::default();
+    popular.city_times.insert(city("London", "uk", 60, true));
+    popular
+        .city_times
+        .insert(city("Tokyo", "japan", 540, false));
+
+    let mut map = HashMap::new();
+    map.insert(String::from("Europa"), europe);
+    map.insert(String::from("Asia"), asia);
+    map.insert(String::from("Popular Cities"), popular);
+    map
+}
+
+fn names(cities: Vec<CityData>) -> Vec<String> {
+    cities.into_iter().map(|c| c.name).collect()
+}
+
+#[test]
+fn no_filters_test() {
+    //London and Tokyo are on two pages but reported once...
+    assert_eq!(
+        names(CityQuery::new().run_on_pages(&pages())),
+        vec![
+            "Berlin",
+            "Kathmandu",
+            "Kolkata",
+            "London",
+            "Reykjavik",
+            "Tokyo"
+        ]
+    );
+}
+
+#[test]
+fn name_filters_test() {
+    assert_eq!(
+        names(CityQuery::new().name_contains("LON").run_on_pages(&pages())),
+        vec!["London"]
+    );
+    //What the name should contain is no regex...
+    assert!(CityQuery::new()
+        .name_contains("L.n")
+        .run_on_pages(&pages())
+        .is_empty());
+    #[cfg(feature = "regex")]
+    assert_eq!(
+        names(
+            CityQuery::new()
+                .name_matches(Regex::new("^K.*a$").unwrap())
+                .run_on_pages(&pages())
+        ),
+        vec!["Kolkata"]
+    );
+}
+
+#[test]
+fn offset_dst_and_country_filters_test() {
+    assert_eq!(
+        names(
+            CityQuery::new()
+                .offset_between(FixedOffset::east(5 * 3600), FixedOffset::east(6 * 3600))
+                .sorted(Sort::ByOffset)
+                .run_on_pages(&pages())
+        ),
+        vec!["Kolkata", "Kathmandu"]
+    );
+    assert_eq!(
+        names(CityQuery::new().dst(true).run_on_pages(&pages())),
+        vec!["Berlin", "London"]
+    );
+    assert_eq!(
+        names(CityQuery::new().country("Iceland").run_on_pages(&pages())),
+        vec!["Reykjavik"]
+    );
+}
+
+#[test]
+fn continent_filter_test() {
+    assert_eq!(
+        names(
+            CityQuery::new()
+                .continent("popular cities")
+                .run_on_pages(&pages())
+        ),
+        vec!["London", "Tokyo"]
+    );
+
+    //A single TimeData has no page-key: the source page recorded in the cities is used...
+    let europe = &pages()["Europa"];
+    assert!(CityQuery::new().continent("Europa").run(europe).is_empty());
+    assert_eq!(CityQuery::new().run(europe).len(), 3);
+
+    let merged = TimeData::merge(&pages(), &ConflictPolicy::Report).unwrap();
+    assert_eq!(
+        names(CityQuery::new().continent("Europa").run(&merged)),
+        vec!["Berlin", "London", "Reykjavik"]
+    );
+}
+
+#[test]
+fn local_time_filter_test() {
+    //At 08:00 UTC it is 09:00 in London, 10:00 in Berlin and 17:00 in Tokyo...
+    let at = Utc.ymd(2020, 8, 21).and_hms(8, 0, 0);
+    assert_eq!(
+        names(
+            CityQuery::new()
+                .local_time_between(NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(17, 0, 0))
+                .at(at)
+                .sorted(Sort::ByOffset)
+                .run_on_pages(&pages())
+        ),
+        vec!["London", "Berlin", "Kolkata", "Kathmandu", "Tokyo"]
+    );
+
+    //A range spanning midnight: at 20:00 UTC it is 20:00 in Reykjavik and 05:00 in Tokyo...
+    let at = Utc.ymd(2020, 8, 21).and_hms(20, 0, 0);
+    assert_eq!(
+        names(
+            CityQuery::new()
+                .local_time_between(NaiveTime::from_hms(21, 0, 0), NaiveTime::from_hms(2, 0, 0))
+                .at(at)
+                .run_on_pages(&pages())
+        ),
+        vec!["Berlin", "Kathmandu", "Kolkata", "London"]
+    );
+}
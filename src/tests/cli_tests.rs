@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use chrono::{Duration, FixedOffset, NaiveDate};
+
+use crate::cache::cache_store;
+use crate::cache_archive::export;
+use crate::cache_store::{CacheStore, MemoryStore};
+use crate::cli::{
+    answer, parse, respond, run, usage, CacheCommand, CliError, Command, ListOptions, Source,
+    EXIT_FAILURE, EXIT_OK, EXIT_USAGE,
+};
+use crate::config::Config;
+use crate::parse_timeanddate_dot_com::{CityData, Sort, TimeData};
+use crate::time_conversion::TimeInput;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+#[test]
+fn parse_commands_test() {
+    //Settings go anywhere and are left to the configuration...
+    assert_eq!(
+        parse(&args(&["use_cache", "fetch", "ttl=120"])),
+        Ok(Command::Fetch)
+    );
+    assert_eq!(
+        parse(&args(&[
+            "list",
+            "--continent",
+            "Europa",
+            "--offset=+05:30",
+            "--no-dst",
+            "--sort",
+            "offset"
+        ])),
+        Ok(Command::List(ListOptions {
+            continent: Some(String::from("Europa")),
+            offset: Some(FixedOffset::east(5 * 3600 + 1800)),
+            dst: Some(false),
+            sort: Some(Sort::ByOffset),
+            ..Default::default()
+        }))
+    );
+    assert_eq!(
+        parse(&args(&[
+            "convert",
+            "2020-08-21 15:00",
+            "Auckland",
+            "new york"
+        ])),
+        Ok(Command::Convert {
+            time: TimeInput::Local(NaiveDate::from_ymd(2020, 8, 21).and_hms(15, 0, 0)),
+            from: String::from("Auckland"),
+            to: String::from("new york"),
+        })
+    );
+    assert_eq!(
+        parse(&args(&["diff", "yesterday.json.gz"])),
+        Ok(Command::Diff {
+            old: Source::Archive(String::from("yesterday.json.gz")),
+            new: Source::Web,
+        })
+    );
+    assert_eq!(
+        parse(&args(&["cache", "export", "cache.json"])),
+        Ok(Command::Cache(CacheCommand::Export(String::from(
+            "cache.json"
+        ))))
+    );
+    assert_eq!(
+        parse(&args(&["serve", "--every=15", "--port", "9090"])),
+        Ok(Command::Serve {
+            port: 9090,
+            every: Some(Duration::minutes(15)),
+        })
+    );
+    assert_eq!(
+        parse(&args(&["help", "convert"])),
+        Ok(Command::Help(Some(String::from("convert"))))
+    );
+    assert_eq!(
+        parse(&args(&["cache", "--help"])),
+        Ok(Command::Help(Some(String::from("cache"))))
+    );
+    assert!(usage().contains("serve"));
+}
+
+#[test]
+fn parse_errors_test() {
+    let error = |a: &[&str]| parse(&args(a)).unwrap_err();
+
+    assert_eq!(
+        error(&["fecth"]),
+        CliError::UnknownCommand(String::from("fecth"))
+    );
+    assert_eq!(
+        error(&["convert", "15:00", "Auckland"]).to_string(),
+        "Missing argument TO for 'convert'"
+    );
+    assert_eq!(
+        error(&["convert", "quarter past three", "Auckland", "Berlin"]).to_string(),
+        "Invalid value 'quarter past three' for TIME: expected now, 2020-08-21T15:00:00Z, '2020-08-21 15:00' or 15:00"
+    );
+    assert_eq!(
+        error(&["list", "--offset", "+25:00"]).to_string(),
+        "Invalid value '+25:00' for --offset: expected an offset like +05:30 or -3"
+    );
+    assert_eq!(
+        error(&["list", "--verbose"]).to_string(),
+        "Unknown option '--verbose' for 'list'"
+    );
+    assert_eq!(
+        error(&["fetch", "Europa"]).to_string(),
+        "Unexpected argument 'Europa' for 'fetch'"
+    );
+    assert_eq!(
+        error(&["cache", "empty"]).to_string(),
+        "Unexpected argument 'empty' for 'cache'"
+    );
+    assert_eq!(
+        error(&["serve", "--port", "http"]).to_string(),
+        "Invalid value 'http' for --port: expected a port number"
+    );
+    if !cfg!(feature = "gui") {
+        assert_eq!(
+            error(&["show"]).to_string(),
+            "'show' needs the 'gui'-feature; this build was made without it"
+        );
+    }
+}
+
+#[test]
+fn respond_test() {
+    let mut pages = HashMap::new();
+    assert_eq!(respond("/", &pages).0, 503);
+
+    pages.insert(String::from("Europa"), TimeData::default());
+    pages.insert(String::from("Asia"), TimeData::default());
+    let (status, body) = respond("/", &pages);
+    assert_eq!(status, 200);
+    let all: HashMap<String, TimeData> = serde_json::from_str(&body).unwrap();
+    assert_eq!(all.len(), 2);
+
+    assert_eq!(respond("/Europa?pretty", &pages).0, 200);
+    //The keys with a space in them can be asked for as well...
+    pages.insert(String::from("Popular Cities"), TimeData::default());
+    assert_eq!(respond("/Popular%20Cities", &pages).0, 200);
+    assert_eq!(respond("/Popular%2", &pages).0, 404);
+    let (status, body) = respond("/Africa", &pages);
+    assert_eq!(status, 404);
+    assert_eq!(body, r#"{"error":"No page 'Africa'"}"#);
+}
+
+#[test]
+fn answer_test() {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let address = listener.local_addr().unwrap();
+    //The client keeps its side open until it has the answer; the server must not wait for more than the headers...
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /Europa HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    });
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), TimeData::default());
+    let (stream, _) = listener.accept().unwrap();
+    answer(stream, &pages).unwrap();
+    assert!(client
+        .join()
+        .unwrap()
+        .starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json"));
+}
+
+fn archived_page(city: &str) -> TimeData {
+    let mut td = TimeData::default();
+    td.city_times.insert(CityData {
+        name: city.to_string(),
+        ..Default::default()
+    });
+    td
+}
+
+fn archive(name: &str, city: &str) -> String {
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), archived_page(city));
+    let store = MemoryStore::new();
+    store.replace_all(&pages).unwrap();
+    let file = env::temp_dir().join(name);
+    export(&store, &file).unwrap();
+    file.to_string_lossy().into_owned()
+}
+
+#[test]
+fn run_exit_codes_test() {
+    let run = |a: &[&str]| run(&args(a), true);
+
+    assert_eq!(run(&["help"]), EXIT_OK);
+    //The warnings of the crate - e.g. about skipped documents - reach the user...
+    assert!(log::log_enabled!(log::Level::Warn));
+    assert_eq!(run(&["convert", "--help"]), EXIT_OK);
+    assert_eq!(run(&["fecth"]), EXIT_USAGE);
+    //The configuration comes from the same arguments as the command...
+    assert_eq!(run(&["list", "cache.tll_minutes=5"]), EXIT_USAGE);
+    //...and only the commands that fetch need the URL list...
+    assert_eq!(run(&["list", "urls_file=no-such-file.txt"]), EXIT_USAGE);
+    let export = env::temp_dir().join("cli_run_test_export.json");
+    let export = export.to_string_lossy();
+    assert_eq!(
+        run(&[
+            "cache",
+            "export",
+            &export,
+            "store=memory",
+            "urls_file=no-such-file.txt"
+        ]),
+        EXIT_OK
+    );
+    fs::remove_file(export.as_ref()).unwrap();
+
+    let old = archive("cli_run_test_old.json", "Berlin");
+    let new = archive("cli_run_test_new.json", "Paris");
+    assert_eq!(run(&["diff", &old, &old]), EXIT_OK);
+    assert_eq!(run(&["diff", &old, &new]), EXIT_FAILURE);
+    assert_eq!(run(&["diff", &old, "no-such-archive.json"]), EXIT_USAGE);
+    fs::remove_file(old).unwrap();
+    fs::remove_file(new).unwrap();
+
+    //The page is served from the memory-cache - and never downloaded - : the city is unknown, not the page...
+    let settings = [
+        "use_cache",
+        "store=memory",
+        "urls.Europa=http://localhost:1/",
+    ];
+    let config = Config::from_sources(None, &[], &args(&settings)).unwrap();
+    cache_store(&config.cache)
+        .put("Europa", &archived_page("Berlin"))
+        .unwrap();
+    let convert =
+        |from: &str| run(&[&["convert", "15:00", from, "Berlin"], &settings[..]].concat());
+    assert_eq!(convert("Berlin"), EXIT_OK);
+    assert_eq!(convert("Atlantis"), EXIT_FAILURE);
+}
@@ -0,0 +1,202 @@
+use std::fs;
+
+use crate::config::{Config, ConfigError, StoreKind, DEFAULT_CONFIG_FILE};
+use crate::parse_timeanddate_dot_com::PageOptions;
+#[cfg(feature = "sqlite")]
+use crate::parse_timeanddate_dot_com::Sort;
+use crate::url_list::ParserKind;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn defaults_test() {
+    let config = Config::from_sources(None, &[], &[]).unwrap();
+    assert_eq!(config.urls_file, "urls.txt");
+    assert!(!config.cache.enabled);
+    assert_eq!(config.cache.store, StoreKind::Mongo);
+    assert_eq!(config.cache.ttl_minutes, 480);
+    assert_eq!(config.cache.max_stale_minutes, 1440);
+    assert_eq!(config.cache.mongo.collection, "city_data");
+    assert_eq!(config.fetch.timeout_seconds, 30);
+    assert!(config.ui.show_clock);
+
+    //The shipped configuration-file holds the defaults...
+    let toml = fs::read_to_string(DEFAULT_CONFIG_FILE).unwrap();
+    let shipped = Config::from_sources(Some((DEFAULT_CONFIG_FILE, &toml)), &[], &[]).unwrap();
+    assert_eq!(
+        toml::to_string(&shipped).unwrap(),
+        toml::to_string(&config).unwrap()
+    );
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn overrides_test() {
+    let toml = r#"
+        [cache]
+        enabled = true
+        store = "sqlite"
+        ttl_minutes = 60
+
+        [fetch]
+        sort = "ByOffset"
+    "#;
+    let file = Some(("test.toml", toml));
+
+    let config = Config::from_sources(file, &[], &[]).unwrap();
+    assert!(config.cache.enabled);
+    assert_eq!(config.cache.store, StoreKind::Sqlite);
+    assert_eq!(config.cache.ttl_minutes, 60);
+    assert!(matches!(config.fetch.sort, Sort::ByOffset));
+
+    //The environment wins from the file; the command-line wins from both...
+    let vars = env(&[
+        ("TIMEANDDATE_CACHE_TTL_MINUTES", "90"),
+        (
+            "TIMEANDDATE_CACHE_MONGO_URI",
+            "mongodb://mongo.example.com/?tls=true",
+        ),
+        ("TIMEANDDATE_UI_SHOW_ANIMATION", "false"),
+        ("PATH", "/usr/bin"),
+        //No settings, yet no errors either...
+        ("TIMEANDDATE_CONFIG", "test.toml"),
+        ("TIMEANDDATE_TEST_MONGO_URI", "mongodb://localhost:27018"),
+    ]);
+    let config = Config::from_sources(file, &vars, &[]).unwrap();
+    assert_eq!(config.cache.ttl_minutes, 90);
+    assert_eq!(
+        config.cache.mongo.uri,
+        "mongodb://mongo.example.com/?tls=true"
+    );
+    assert!(!config.ui.show_animation);
+
+    let config = Config::from_sources(
+        file,
+        &vars,
+        &args(&[
+            "cache.ttl_minutes=120",
+            "store=file",
+            "cache_file=/tmp/cache.json",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(config.cache.ttl_minutes, 120);
+    assert_eq!(config.cache.store, StoreKind::File);
+    assert_eq!(config.cache.file_path, "/tmp/cache.json");
+
+    //Inline urls replace the URL list...
+    let config =
+        Config::from_sources(None, &[], &args(&["urls.Europa=https://example.com/"])).unwrap();
+    assert_eq!(config.urls().len(), 1);
+    assert_eq!(config.urls()["Europa"], "https://example.com/");
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn short_hands_test() {
+    let config = Config::from_sources(
+        None,
+        &[],
+        &args(&["use_cache", "ttl=15", "max_stale=0", "store=sqlite"]),
+    )
+    .unwrap();
+    assert!(config.cache.enabled);
+    assert_eq!(config.cache.store, StoreKind::Sqlite);
+    assert_eq!(config.cache.ttl_minutes, 15);
+    assert_eq!(config.cache.max_stale_minutes, 0);
+
+    //Merely containing the word 'cache' no longer enables caching; nor do options of the test-harness matter...
+    let config = Config::from_sources(
+        None,
+        &[],
+        &args(&["no_cache_please", "cache_test", "--test-threads=1"]),
+    )
+    .unwrap();
+    assert!(!config.cache.enabled);
+}
+
+#[test]
+fn errors_test() {
+    let error = |file: Option<(&str, &str)>, vars: &[(&str, &str)], a: &[&str]| {
+        Config::from_sources(file, &env(vars), &args(a))
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        error(None, &[], &["ttl=eight hours"]),
+        "Invalid value 'eight hours' for setting 'cache.ttl_minutes': expected a whole number"
+    );
+    assert_eq!(
+        error(None, &[], &["ttl=0"]),
+        "Invalid value '0' for setting 'cache.ttl_minutes': expected a positive number of minutes"
+    );
+    assert_eq!(
+        error(None, &[("TIMEANDDATE_CACHE_ENABLED", "yes")], &[]),
+        "Invalid value 'yes' for setting 'cache.enabled': expected true or false"
+    );
+    assert!(error(None, &[], &["cache.tll_minutes=5"]).starts_with(
+        "Unknown setting 'cache.tll_minutes' in the command-line; known settings are: "
+    ));
+    assert!(error(None, &[("TIMEANDDATE_TTL", "5")], &[])
+        .starts_with("Unknown setting 'TIMEANDDATE_TTL' in the environment"));
+    assert!(error(None, &[], &["store=redis"]).contains("unknown variant `redis`"));
+    //The URL list is only checked when pages are to be fetched...
+    let no_list = Config::from_sources(None, &[], &args(&["urls_file=no-such-file.txt"])).unwrap();
+    assert!(no_list
+        .validate_urls()
+        .unwrap_err()
+        .to_string()
+        .starts_with(
+            "Invalid value 'no-such-file.txt' for setting 'urls_file': expected an existing file"
+        ));
+    //Nor can a build without the 'sqlite'-feature cache in SQLite...
+    if !cfg!(feature = "sqlite") {
+        assert_eq!(
+            error(None, &[], &["use_cache", "store=sqlite"]),
+            "Invalid value 'sqlite' for setting 'cache.store': expected file, mongo or memory; this build has no SQLite-support"
+        );
+    }
+    //A build without the 'mongo'-feature cannot cache in MongoDB...
+    if !cfg!(feature = "mongo") {
+        assert_eq!(
+            error(None, &[], &["use_cache"]),
+            "Invalid value 'mongo' for setting 'cache.store': expected file, sqlite or memory; this build has no MongoDB-support"
+        );
+    }
+
+    let typo = error(Some(("test.toml", "[cache]\nttl = 5\n")), &[], &[]);
+    assert!(typo.starts_with("Invalid configuration in test.toml: unknown field `ttl`"));
+    assert!(matches!(
+        Config::from_sources(Some(("test.toml", "[cache")), &[], &[]),
+        Err(ConfigError::Malformed { .. })
+    ));
+}
+
+#[test]
+fn page_options_test() {
+    //The parser and locale of a page in the URL list end up in the fetch-options...
+    let file = std::env::temp_dir().join("config_test_page_options.txt");
+    fs::write(
+        &file,
+        "Europa=https://www.timeanddate.com/worldclock/?continent=europe locale=nl parser=worldclock\n\
+         Asia=https://www.timeanddate.com/worldclock/?continent=asia",
+    )
+    .unwrap();
+    let setting = format!("urls_file={}", file.display());
+    let config = Config::from_sources(None, &[], &args(&[setting.as_str()])).unwrap();
+    let options = config.fetch_options();
+    fs::remove_file(&file).unwrap();
+
+    assert_eq!(options.pages["Europa"].locale.as_deref(), Some("nl"));
+    assert_eq!(options.pages["Europa"].parser, ParserKind::WorldClock);
+    assert_eq!(options.pages["Asia"], PageOptions::default());
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use chrono::{Duration, FixedOffset, TimeZone, Utc};
+
+use crate::history::{changes, FileHistory, HistoryStore, Retention};
+use crate::parse_timeanddate_dot_com::{CityData, TimeData, UtcOffset};
+
+fn pages(offset_hours: i32, is_dls: bool) -> HashMap<String, TimeData> {
+    let mut td = TimeData::default();
+    for (name, offset) in [("Amsterdam", offset_hours), ("Lagos", 1)].iter() {
+        td.city_times.insert(CityData {
+            name: name.to_string(),
+            utc_offset: UtcOffset::from(FixedOffset::east(offset * 3600)),
+            is_dls: is_dls && *name == "Amsterdam",
+            ..Default::default()
+        });
+    }
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), td);
+    pages
+}
+
+#[test]
+fn file_history_timeline_test() {
+    let file = env::temp_dir().join("history_timeline_test.jsonl");
+    let _ = fs::remove_file(&file);
+    let history = FileHistory::new(&file);
+    assert!(history.timeline("Amsterdam").unwrap().is_empty());
+
+    //Four refreshes around the switch to summer time; appended out of order on purpose...
+    let day = |d: u32| Utc.ymd(2020, 3, d).and_hms(12, 0, 0);
+    history.append(day(28), &pages(1, false)).unwrap();
+    history.append(day(30), &pages(2, true)).unwrap();
+    history.append(day(29), &pages(2, true)).unwrap();
+    history.append(day(27), &pages(1, false)).unwrap();
+
+    let timeline = history.timeline(" amsterdam ").unwrap();
+    assert_eq!(
+        timeline.iter().map(|p| p.taken_at).collect::<Vec<_>>(),
+        vec![day(27), day(28), day(29), day(30)]
+    );
+    assert!(timeline.iter().all(|p| p.page == "Europa"));
+
+    //Only the switch itself is a change...
+    let changed = changes(&timeline);
+    assert_eq!(changed.len(), 2);
+    assert_eq!(changed[1].taken_at, day(29));
+    assert_eq!(changed[1].utc_offset(), FixedOffset::east(2 * 3600));
+    assert!(changed[1].is_dls);
+    assert_eq!(changes(&history.timeline("Lagos").unwrap()).len(), 1);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn file_history_retention_test() {
+    let file = env::temp_dir().join("history_retention_test.jsonl");
+    let _ = fs::remove_file(&file);
+    let history = FileHistory::new(&file);
+
+    let now = Utc::now();
+    for days_ago in 0..10 {
+        history
+            .append(now - Duration::days(days_ago), &pages(1, false))
+            .unwrap();
+    }
+    assert_eq!(history.prune(&Retention::default()).unwrap(), 0);
+
+    let week = Retention {
+        max_age: Some(Duration::days(7) - Duration::minutes(1)),
+        max_snapshots: None,
+    };
+    assert_eq!(history.prune(&week).unwrap(), 3);
+    assert_eq!(history.timeline("Lagos").unwrap().len(), 7);
+
+    //Both limits apply; the newest snapshots are kept...
+    let three = Retention {
+        max_snapshots: Some(3),
+        ..week
+    };
+    assert_eq!(history.prune(&three).unwrap(), 4);
+    let timeline = history.timeline("Lagos").unwrap();
+    assert_eq!(timeline.len(), 3);
+    assert_eq!(timeline.last().unwrap().taken_at, now);
+
+    fs::remove_file(&file).unwrap();
+}
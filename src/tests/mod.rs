@@ -1,3 +1,20 @@
 #[cfg(test)]
+#[cfg(all(feature = "archive", feature = "sqlite"))]
+mod cache_archive_tests;
+#[cfg(feature = "http-fetch")]
+mod cache_refresher_tests;
+mod cache_store_tests;
+#[cfg(feature = "config")]
 mod cache_test;
+mod city_query_tests;
+#[cfg(feature = "cli")]
+mod cli_tests;
+#[cfg(feature = "config")]
+mod config_tests;
+mod history_tests;
 mod parse_timeanddate_dot_com_tests;
+mod snapshot_diff_tests;
+mod time_and_date_data_tests;
+mod time_conversion_tests;
+#[cfg(feature = "config")]
+mod url_list_tests;
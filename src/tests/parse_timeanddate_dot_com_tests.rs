@@ -1,9 +1,19 @@
 #![warn(rust_2018_idioms)]
-#![warn(warnings)]
 
-use chrono::{DateTime, Timelike};
+#[cfg(feature = "http-fetch")]
+use std::collections::HashMap;
+#[cfg(feature = "http-fetch")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "http-fetch")]
+use std::net::TcpListener;
+#[cfg(feature = "http-fetch")]
+use std::thread;
 
-use crate::parse_timeanddate_dot_com::{city_utc_offset, DayOfWeek};
+use chrono::{DateTime, TimeZone, Timelike, Utc};
+
+use crate::parse_timeanddate_dot_com::{city_utc_offset, parse_page, DayOfWeek, Sort};
+#[cfg(feature = "http-fetch")]
+use crate::parse_timeanddate_dot_com::{try_download_time_data, FetchOptions, PageOptions};
 
 #[test]
 fn city_utc_offset_test() {
@@ -154,3 +164,76 @@ fn city_utc_offset_test() {
         String::from("-01:02")
     );
 }
+
+#[test]
+fn parse_page_test() {
+    //A page the application downloaded itself: no 'http-fetch'-feature needed...
+    let body = String::from(
+        r#"<html><body><table><tr>
+        <td><a href="/worldclock/new-zealand/auckland">Auckland</a><span id=p26s class=wds> *</span></td>
+        <td id=p26 class=rbi>Thu 9:00 p.m.</td>
+        </tr></table></body></html>"#,
+    );
+    let fetched_at = Utc.ymd(2020, 1, 2).and_hms(8, 0, 0);
+    let td = parse_page(
+        "https://www.timeanddate.com/worldclock/",
+        body,
+        fetched_at,
+        Sort::ByName,
+    )
+    .unwrap();
+
+    assert_eq!(td.fetched_at, fetched_at);
+    let auckland = td.city_times.iter().find(|c| c.name == "Auckland").unwrap();
+    assert!(auckland.is_dls);
+    assert_eq!(auckland.utc_offset.get().to_string(), "+13:00");
+    assert_eq!(
+        auckland.url,
+        "https://www.timeanddate.com/worldclock/new-zealand/auckland"
+    );
+    assert!(td.city_times.iter().any(|c| c.name == "UTC"));
+}
+
+#[test]
+#[cfg(feature = "http-fetch")]
+fn page_options_test() {
+    //A web-server of one request that tells the headers it was sent...
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut headers = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+                break;
+            }
+            headers.push_str(&line);
+        }
+        let body = "<html><body></body></html>";
+        write!(
+            &stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        headers.to_lowercase()
+    });
+
+    //The page's locale is asked for...
+    let mut options = FetchOptions::default();
+    options.pages.insert(
+        String::from("Europa"),
+        PageOptions {
+            locale: Some(String::from("nl")),
+            ..Default::default()
+        },
+    );
+    let mut urls = HashMap::new();
+    urls.insert(String::from("Europa"), url);
+    let pages = try_download_time_data(options, &urls);
+    assert!(pages.contains_key("Europa"));
+    assert!(server.join().unwrap().contains("accept-language: nl"));
+}
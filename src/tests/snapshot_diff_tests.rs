@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use chrono::FixedOffset;
+
+use crate::parse_timeanddate_dot_com::{CityData, TimeData, UtcOffset};
+use crate::snapshot_diff::diff;
+
+fn page(cities: &[(&str, i32, bool)]) -> TimeData {
+    let mut td = TimeData::default();
+    for (name, offset_hours, is_dls) in cities {
+        td.city_times.insert(CityData {
+            name: name.to_string(),
+            utc_offset: UtcOffset::from(FixedOffset::east(offset_hours * 3600)),
+            is_dls: *is_dls,
+            ..Default::default()
+        });
+    }
+    td
+}
+
+#[test]
+fn no_changes_test() {
+    let mut old = HashMap::new();
+    old.insert(String::from("Europa"), page(&[("Berlin", 2, true)]));
+
+    let d = diff(&old, &old.clone());
+    assert!(d.is_empty());
+    assert_eq!(d.to_string(), "No changes in the time-data.");
+}
+
+#[test]
+fn dst_switch_test() {
+    //Berlin leaves DST; Lisbon is new, Madrid is gone...
+    let mut old = HashMap::new();
+    old.insert(
+        String::from("Europa"),
+        page(&[
+            ("Berlin", 2, true),
+            ("London", 1, true),
+            ("Madrid", 2, true),
+        ]),
+    );
+    old.insert(String::from("Asia"), page(&[("Tokyo", 9, false)]));
+
+    let mut new = HashMap::new();
+    new.insert(
+        String::from("Europa"),
+        page(&[
+            ("Berlin", 1, false),
+            ("London", 1, true),
+            ("Lisbon", 0, false),
+        ]),
+    );
+    new.insert(String::from("Asia"), page(&[("Tokyo", 9, false)]));
+    new.insert(String::from("Afrika"), page(&[("Cairo", 2, false)]));
+
+    let d = diff(&old, &new);
+    assert_eq!(d.pages.keys().collect::<Vec<_>>(), vec!["Afrika", "Europa"]);
+
+    let europe = &d.pages["Europa"];
+    assert_eq!(europe.added[0].name, "Lisbon");
+    assert_eq!(europe.removed[0].name, "Madrid");
+    assert_eq!(europe.offset_changes.len(), 1);
+    assert_eq!(europe.offset_changes[0].city, "Berlin");
+    assert_eq!(europe.offset_changes[0].new.get().to_string(), "+01:00");
+    assert_eq!(europe.dst_flips.len(), 1);
+    assert!(europe.dst_flips[0].was_dls && !europe.dst_flips[0].is_dls);
+
+    assert_eq!(d.pages["Afrika"].added[0].name, "Cairo");
+    assert_eq!(
+        d.to_string(),
+        "Changes in Afrika:\n\t+ Cairo (+02:00)\nChanges in Europa:\n\t+ Lisbon (+00:00)\n\t- Madrid (+02:00)\n\t~ Berlin: offset +02:00 -> +01:00\n\t~ Berlin: DST -> Winter Time\n"
+    );
+}
@@ -0,0 +1,297 @@
+#[cfg(feature = "mongo")]
+use bson::{doc, from_bson, Bson};
+use chrono::{FixedOffset, TimeZone, Utc};
+use http::Uri;
+
+#[cfg(feature = "mongo")]
+use crate::mongo_api::{
+    migrate_city_document, migrate_document, migrate_legacy_timestamps, SCHEMA_VERSION,
+};
+#[cfg(feature = "mongo")]
+use crate::parse_timeanddate_dot_com::unix_epoch;
+use crate::parse_timeanddate_dot_com::{
+    CityData, ConflictPolicy, Sort, TimeData, UriWrapper, UtcOffset,
+};
+
+#[test]
+#[cfg(feature = "mongo")]
+fn legacy_document_migration_test() {
+    //A document as stored before the typed timestamps: no 'fetched_at' nor 'page_reference_time'...
+    let mut legacy = doc! {
+        "page_uri": "https://www.timeanddate.com/worldclock/?continent=europe",
+        "node_data": "",
+        "city_times": [],
+        "last_updated": "2020-08-20T10:00:00+00:00",
+    };
+    migrate_legacy_timestamps(&mut legacy);
+    let td = from_bson::<TimeData>(Bson::from(legacy)).unwrap();
+
+    let expected = Utc.ymd(2020, 8, 20).and_hms(10, 0, 0);
+    assert_eq!(td.last_updated, expected);
+    assert_eq!(td.fetched_at, expected);
+    assert_eq!(td.page_reference_time, expected);
+
+    //...and an empty 'last_updated' - what TimeData::default() used to store - becomes the epoch...
+    let mut legacy = doc! {
+        "page_uri": "https://www.timeanddate.com/worldclock/?continent=europe",
+        "node_data": "",
+        "city_times": [],
+        "last_updated": "",
+    };
+    migrate_legacy_timestamps(&mut legacy);
+    let td = from_bson::<TimeData>(Bson::from(legacy)).unwrap();
+    assert_eq!(td.last_updated, unix_epoch());
+    assert_eq!(td.fetched_at, unix_epoch());
+}
+
+#[test]
+#[cfg(feature = "mongo")]
+fn schema_version_migration_test() {
+    let mut legacy = doc! {
+        "page_uri": "https://www.timeanddate.com/worldclock/?continent=europe",
+        "node_data": "",
+        "city_times": [],
+        "last_updated": "2020-08-20T10:00:00+00:00",
+    };
+    assert!(migrate_document(&mut legacy));
+    assert_eq!(legacy.get_i32("schema_version").unwrap(), SCHEMA_VERSION);
+    assert!(from_bson::<TimeData>(Bson::from(legacy.clone())).is_ok());
+
+    //Up to date already: nothing to do...
+    assert!(!migrate_document(&mut legacy));
+
+    //...and a document of a newer version is left as it is.
+    let mut newer = doc! {"schema_version": SCHEMA_VERSION + 1, "last_updated": "tomorrow"};
+    assert!(!migrate_document(&mut newer));
+    assert_eq!(newer.get_str("last_updated").unwrap(), "tomorrow");
+}
+
+#[test]
+#[cfg(feature = "mongo")]
+fn city_schema_version_migration_test() {
+    //A city-document without a version may lack the offset in seconds the queries filter on...
+    let mut legacy = doc! {
+        "page_key": "Asia",
+        "id": 1,
+        "name": "Kolkata",
+        "time_string": "",
+        "utc_offset": "+05:30",
+        "is_dls": false,
+        "url": "https://www.timeanddate.com/worldclock/india/kolkata",
+        "sort": "ByName",
+    };
+    assert!(migrate_city_document(&mut legacy));
+    assert_eq!(legacy.get_i32("schema_version").unwrap(), SCHEMA_VERSION);
+    assert_eq!(legacy.get_i32("utc_offset_seconds").unwrap(), 19800);
+    assert!(!migrate_city_document(&mut legacy));
+
+    //An unreadable one only gets its version...
+    let mut garbage = doc! {"name": 42};
+    assert!(migrate_city_document(&mut garbage));
+    assert!(!garbage.contains_key("utc_offset_seconds"));
+}
+
+///A page with a few cities that exercise the custom (de)serializers: negative, fractional and zero offsets...
+fn sample_time_data() -> TimeData {
+    let mut td = TimeData {
+        page_uri: UriWrapper::new(
+            "https://www.timeanddate.com/worldclock/?continent=australasia"
+                .parse::<Uri>()
+                .unwrap(),
+        ),
+        last_updated: Utc.ymd(2020, 8, 20).and_hms(10, 0, 0),
+        fetched_at: Utc.ymd(2020, 8, 20).and_hms(9, 59, 0),
+        page_reference_time: Utc.ymd(2020, 8, 20).and_hms(9, 58, 0),
+        ..Default::default()
+    };
+    for (id, name, offset, is_dls) in &[
+        (26, "Auckland", 12 * 3600, false),
+        (103, "Adelaide", 9 * 3600 + 1800, false),
+        (-1, "UTC", 0, false),
+        (99, "St. John's", -(2 * 3600 + 1800), true),
+        (7, "Marquesas", -(9 * 3600 + 1800), false),
+    ] {
+        td.city_times.insert(CityData {
+            id: *id,
+            name: name.to_string(),
+            time_string: String::from("Thu 10:00 pm"),
+            utc_offset: UtcOffset::from(FixedOffset::east(*offset)),
+            is_dls: *is_dls,
+            url: format!("https://www.timeanddate.com/worldclock/{}", name),
+            sort: Sort::ByOffset,
+            source_page: Some(String::from("Australasia")),
+        });
+    }
+    td
+}
+
+///Compares all the fields that make up a page, CityData's PartialEq only looks at the name...
+fn assert_same(expected: &TimeData, actual: &TimeData) {
+    assert_eq!(expected.page_uri(), actual.page_uri());
+    assert_eq!(expected.node_data, actual.node_data);
+    assert_eq!(expected.last_updated, actual.last_updated);
+    assert_eq!(expected.fetched_at, actual.fetched_at);
+    assert_eq!(expected.page_reference_time, actual.page_reference_time);
+    assert_eq!(
+        expected
+            .city_times
+            .iter()
+            .map(|c| format!("{}", c))
+            .collect::<Vec<_>>(),
+        actual
+            .city_times
+            .iter()
+            .map(|c| format!("{}", c))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn json_round_trip_test() {
+    let td = sample_time_data();
+    let json = serde_json::to_string(&td).unwrap();
+    assert_same(&td, &serde_json::from_str::<TimeData>(&json).unwrap());
+}
+
+#[test]
+#[cfg(feature = "mongo")]
+fn bson_round_trip_test() {
+    let td = sample_time_data();
+    let bs = bson::to_bson(&td).unwrap();
+    assert_same(&td, &from_bson::<TimeData>(bs).unwrap());
+}
+
+#[test]
+#[cfg(feature = "config")]
+fn toml_round_trip_test() {
+    let td = sample_time_data();
+    let toml = toml::to_string(&td).unwrap();
+    assert_same(&td, &toml::from_str::<TimeData>(&toml).unwrap());
+}
+
+#[test]
+fn bincode_round_trip_test() {
+    let td = sample_time_data();
+    let bytes = bincode::serialize(&td).unwrap();
+    assert_same(&td, &bincode::deserialize::<TimeData>(&bytes).unwrap());
+}
+
+#[test]
+fn malformed_values_are_errors_test() {
+    //Bad offsets and URIs are reported as errors instead of panicking...
+    assert!(serde_json::from_str::<UtcOffset>("\"+25:99\"").is_err());
+    assert!(serde_json::from_str::<UtcOffset>("\"\"").is_err());
+    assert!(serde_json::from_str::<UriWrapper>("\"\"").is_err());
+    assert!(serde_json::from_str::<UriWrapper>("\"http://in valid\"").is_err());
+
+    assert_eq!(
+        serde_json::from_str::<UtcOffset>("\"-00:45\"")
+            .unwrap()
+            .get()
+            .to_string(),
+        "-00:45"
+    );
+}
+
+fn page(uri: &str, fetched_at_hour: u32, cities: &[(&str, i32, bool)]) -> TimeData {
+    let mut td = TimeData {
+        page_uri: UriWrapper::new(uri.parse::<Uri>().unwrap()),
+        last_updated: Utc.ymd(2020, 8, 20).and_hms(fetched_at_hour, 0, 0),
+        fetched_at: Utc.ymd(2020, 8, 20).and_hms(fetched_at_hour, 0, 0),
+        page_reference_time: Utc.ymd(2020, 8, 20).and_hms(fetched_at_hour, 0, 0),
+        ..Default::default()
+    };
+    for (name, offset_hours, is_dls) in cities {
+        td.city_times.insert(CityData {
+            name: name.to_string(),
+            utc_offset: UtcOffset::from(FixedOffset::east(offset_hours * 3600)),
+            is_dls: *is_dls,
+            ..Default::default()
+        });
+    }
+    td
+}
+
+fn conflicting_pages() -> Vec<(&'static str, TimeData)> {
+    vec![
+        (
+            "Europa",
+            page(
+                "https://www.timeanddate.com/worldclock/?continent=europe",
+                10,
+                &[("London", 1, true), ("Berlin", 2, true)],
+            ),
+        ),
+        (
+            "Popular Cities",
+            page(
+                "https://www.timeanddate.com/worldclock/?low=4",
+                8,
+                &[("London", 0, false), ("Tokyo", 9, false)],
+            ),
+        ),
+    ]
+}
+
+fn offset_of(td: &TimeData, name: &str) -> (String, Option<String>) {
+    let city = td.city_times.iter().find(|c| c.name == name).unwrap();
+    (city.utc_offset.get().to_string(), city.source_page.clone())
+}
+
+#[test]
+fn merge_test() {
+    let pages = conflicting_pages();
+    let pages = pages.iter().map(|(k, td)| (*k, td));
+
+    let merged = TimeData::merge(pages.clone(), &ConflictPolicy::PreferNewest).unwrap();
+    assert_eq!(merged.city_times.len(), 3);
+    assert_eq!(
+        offset_of(&merged, "London"),
+        (String::from("+01:00"), Some(String::from("Europa")))
+    );
+    assert_eq!(
+        offset_of(&merged, "Tokyo"),
+        (String::from("+09:00"), Some(String::from("Popular Cities")))
+    );
+    //The merged data is as old as its oldest page...
+    assert_eq!(merged.last_updated, Utc.ymd(2020, 8, 20).and_hms(8, 0, 0));
+    assert_eq!(merged.page_uri(), &Uri::default());
+
+    let merged = TimeData::merge(
+        pages.clone(),
+        &ConflictPolicy::PreferPage(String::from("Popular Cities")),
+    )
+    .unwrap();
+    assert_eq!(
+        offset_of(&merged, "London"),
+        (String::from("+00:00"), Some(String::from("Popular Cities")))
+    );
+
+    //The conflicts the policy resolved are returned all the same...
+    let (_, resolved) = TimeData::merge_resolving(pages.clone(), &ConflictPolicy::PreferNewest);
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(
+        resolved[0].to_string(),
+        "Merge-conflict for London: kept +01:00 from Some(\"Europa\"); rejected +00:00 from Some(\"Popular Cities\")"
+    );
+
+    let conflicts = TimeData::merge(pages, &ConflictPolicy::Report).unwrap_err();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].city, "London");
+    assert_eq!(conflicts[0].kept.source_page, Some(String::from("Europa")));
+    assert_eq!(
+        conflicts[0].rejected.source_page,
+        Some(String::from("Popular Cities"))
+    );
+}
+
+#[test]
+fn merge_single_page_test() {
+    let pages = conflicting_pages();
+    let merged = TimeData::merge(vec![(pages[0].0, &pages[0].1)], &ConflictPolicy::Report).unwrap();
+    assert_eq!(
+        merged.page_uri().to_string(),
+        "https://www.timeanddate.com/worldclock/?continent=europe"
+    );
+    assert_eq!(merged.last_updated, pages[0].1.last_updated);
+}
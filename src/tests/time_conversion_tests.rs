@@ -0,0 +1,98 @@
+use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+
+use crate::parse_timeanddate_dot_com::{CityData, TimeData, UtcOffset};
+use crate::time_conversion::{convert, convert_by_name, ConversionError, TimeInput};
+
+fn city(name: &str, offset_seconds: i32) -> CityData {
+    CityData {
+        name: name.to_string(),
+        utc_offset: UtcOffset::from(FixedOffset::east(offset_seconds)),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn day_rollover_test() {
+    //Auckland is 10 hours ahead of Berlin in the northern summer...
+    let auckland = city("Auckland", 12 * 3600);
+    let berlin = city("Berlin", 2 * 3600);
+    let early_morning = NaiveDate::from_ymd(2020, 8, 21).and_hms(7, 30, 0);
+
+    let c = convert(TimeInput::Local(early_morning), &auckland, &berlin).unwrap();
+    assert!(c.used_iana_zones);
+    assert_eq!(c.to.to_string(), "2020-08-20 21:30:00 +02:00");
+    assert_eq!(c.day_rollover, -1);
+
+    let c = convert(TimeInput::Local(c.to.naive_local()), &berlin, &auckland).unwrap();
+    assert_eq!(c.to.to_string(), "2020-08-21 07:30:00 +12:00");
+    assert_eq!(c.day_rollover, 1);
+}
+
+#[test]
+fn instant_test() {
+    let instant = Utc.ymd(2020, 1, 3).and_hms(23, 30, 0);
+    let c = convert(
+        TimeInput::Instant(instant),
+        &city("UTC", 0),
+        &city("Auckland", 0),
+    )
+    .unwrap();
+    //In January Auckland observes DST; the IANA-zone knows, regardless of the scraped offset...
+    assert_eq!(c.from.to_string(), "2020-01-03 23:30:00 +00:00");
+    assert_eq!(c.to.to_string(), "2020-01-04 12:30:00 +13:00");
+}
+
+#[test]
+fn scraped_offset_fallback_test() {
+    //No IANA-zone is named after these cities: the scraped offsets are used...
+    let somewhere = city("Somewhere Nice", 5 * 3600 + 1800);
+    let elsewhere = city("Elsewhere Else", -(3 * 3600 + 2700));
+    let noon = NaiveDate::from_ymd(2020, 8, 21).and_hms(12, 0, 0);
+
+    let c = convert(TimeInput::Local(noon), &somewhere, &elsewhere).unwrap();
+    assert!(!c.used_iana_zones);
+    assert_eq!(c.to.to_string(), "2020-08-21 02:45:00 -03:45");
+    assert_eq!(c.later_alternative, None);
+}
+
+#[test]
+fn dst_ambiguous_and_nonexistent_test() {
+    let berlin = city("Berlin", 3600);
+    let auckland = city("Auckland", 13 * 3600);
+
+    //On 2020-10-25 02:30 happened twice in Berlin...
+    let ambiguous = NaiveDate::from_ymd(2020, 10, 25).and_hms(2, 30, 0);
+    let c = convert(TimeInput::Local(ambiguous), &berlin, &auckland).unwrap();
+    assert_eq!(c.to.to_string(), "2020-10-25 13:30:00 +13:00");
+    assert_eq!(
+        c.later_alternative.unwrap().to_string(),
+        "2020-10-25 14:30:00 +13:00"
+    );
+
+    //...whereas on 2020-03-29 02:30 never happened...
+    let nonexistent = NaiveDate::from_ymd(2020, 3, 29).and_hms(2, 30, 0);
+    assert_eq!(
+        convert(TimeInput::Local(nonexistent), &berlin, &auckland),
+        Err(ConversionError::NonexistentLocalTime {
+            city: String::from("Berlin"),
+            local: nonexistent
+        })
+    );
+}
+
+#[test]
+fn convert_by_name_test() {
+    let mut td = TimeData::default();
+    td.city_times.insert(city("Auckland", 12 * 3600));
+    td.city_times.insert(city("New York", -4 * 3600));
+    let instant = Utc.ymd(2020, 8, 21).and_hms(0, 0, 0);
+
+    let c = convert_by_name(TimeInput::Instant(instant), &td, "new york", "Auckland").unwrap();
+    assert_eq!(c.from.to_string(), "2020-08-20 20:00:00 -04:00");
+    assert_eq!(c.to.to_string(), "2020-08-21 12:00:00 +12:00");
+
+    assert_eq!(
+        convert_by_name(TimeInput::Instant(instant), &td, "Atlantis", "Auckland"),
+        Err(ConversionError::UnknownCity(String::from("Atlantis")))
+    );
+}
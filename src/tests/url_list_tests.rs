@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use chrono::Duration;
+
+use crate::cache::urls_from;
+use crate::url_list::{read, read_with, ParserKind};
+
+fn vars(name: &str) -> Option<String> {
+    match name {
+        "TEST_HOME_URL" => Some(String::from(
+            "https://www.timeanddate.com/worldclock/?low=5",
+        )),
+        _ => None,
+    }
+}
+
+#[test]
+fn rich_url_list_test() {
+    let entries = read_with(Path::new("./src/tests/test-urls-rich.txt"), &vars).unwrap();
+    assert_eq!(
+        entries.iter().map(|e| e.key.as_str()).collect::<Vec<_>>(),
+        vec!["Popular Cities", "Europa", "Asia", "Home"]
+    );
+
+    //The included entry knows where it came from...
+    assert!(entries[0].file.ends_with("test-urls-included.txt"));
+    assert_eq!(entries[0].line, 1);
+    assert_eq!(entries[0].group, None);
+
+    let europa = &entries[1];
+    assert_eq!(europa.group.as_deref(), Some("Continents"));
+    assert_eq!(europa.ttl, Some(Duration::minutes(120)));
+    assert_eq!(europa.locale.as_deref(), Some("en"));
+    assert!(europa.enabled);
+    assert_eq!(europa.line, 5);
+
+    assert!(!entries[2].enabled);
+
+    let home = &entries[3];
+    assert_eq!(home.group.as_deref(), Some("Home"));
+    assert_eq!(home.url, "https://www.timeanddate.com/worldclock/?low=5");
+    assert_eq!(home.parser, ParserKind::WorldClock);
+    assert_eq!(home.locale, None);
+}
+
+#[test]
+fn disabled_urls_are_left_out_test() {
+    std::env::set_var("TEST_HOME_URL", "H");
+    let mut keys: Vec<String> = urls_from("./src/tests/test-urls-rich.txt")
+        .into_keys()
+        .collect();
+    keys.sort();
+    assert_eq!(keys, vec!["Europa", "Home", "Popular Cities"]);
+}
+
+#[test]
+fn malformed_url_list_test() {
+    //Bad entries used to be skipped silently...
+    assert_eq!(
+        read("./src/tests/test-urls-malformed.txt")
+            .unwrap_err()
+            .to_string(),
+        "./src/tests/test-urls-malformed.txt:3: malformed entry 'Bibbidi=': the url is missing"
+    );
+
+    let error = |content: &str| {
+        let file = std::env::temp_dir().join(format!("url_list_test_{}.txt", content.len()));
+        std::fs::write(&file, content).unwrap();
+        let message = read_with(&file, &vars).unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        (message.line, message.message)
+    };
+    assert_eq!(
+        error("Babbidi"),
+        (
+            1,
+            String::from("malformed entry 'Babbidi': expected key=url")
+        )
+    );
+    assert_eq!(
+        error("\nEuropa=E ttl=soon"),
+        (
+            2,
+            String::from("invalid ttl 'soon': expected a positive number of minutes")
+        )
+    );
+    assert_eq!(
+        error("Europa=E colour=blue"),
+        (
+            1,
+            String::from("unknown option 'colour': expected ttl, parser, locale or enabled")
+        )
+    );
+    assert_eq!(
+        error("Europa=E parser=cityclock"),
+        (
+            1,
+            String::from("unknown parser 'cityclock': expected worldclock")
+        )
+    );
+    assert_eq!(
+        error("Europa=E\n\n\nEuropa=F"),
+        (4, String::from("duplicate key 'Europa'"))
+    );
+    assert_eq!(
+        error("Europa=${NO_SUCH_VARIABLE}"),
+        (
+            1,
+            String::from("environment-variable 'NO_SUCH_VARIABLE' is not set")
+        )
+    );
+
+    //Commented out entries are not substituted...
+    let file = std::env::temp_dir().join("url_list_test_commented_out.txt");
+    std::fs::write(
+        &file,
+        "# Home=${NO_SUCH_VARIABLE}\n// Work=${NO_SUCH_VARIABLE}\nEuropa=E",
+    )
+    .unwrap();
+    let entries = read_with(&file, &vars);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(entries.unwrap().len(), 1);
+
+    let self_include = read("./src/tests/test-urls-self-include.txt").unwrap_err();
+    assert_eq!(self_include.line, 1);
+    assert!(self_include.message.ends_with("it includes itself"));
+}
@@ -1,5 +1,5 @@
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 pub use std::convert::From;
 use std::fmt;
 use std::fmt::Display;
@@ -7,20 +7,31 @@ use std::str;
 use time;
 use String;
 
-use chrono::{FixedOffset, Utc, Weekday};
+use chrono::{DateTime, Duration, FixedOffset, TimeZone, Utc, Weekday};
 pub use http::Uri;
-use mongodb::bson::Bson;
 use serde::de::{Error, Unexpected, Visitor};
 use serde::export::Formatter;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::str::FromStr;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeData {
     pub page_uri: UriWrapper,
     pub node_data: String,
+    ///When this page was last stored in the cache.
+    #[serde(with = "rfc3339")]
+    pub last_updated: DateTime<Utc>,
+    ///When this page was downloaded from the website.
+    #[serde(with = "rfc3339")]
+    pub fetched_at: DateTime<Utc>,
+    ///The UTC-instant the page's (relative) city-times were read against to determine the UTC-offsets.
+    #[serde(with = "rfc3339")]
+    pub page_reference_time: DateTime<Utc>,
+    ///Set when a refresh failed and this - expired - page was served from the cache instead.
+    #[serde(skip)]
+    pub stale: bool,
+    ///Kept as the last field: formats like TOML demand that tables come after all plain values.
     pub city_times: BTreeSet<CityData>,
-    pub last_updated: String,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +43,9 @@ pub struct CityData {
     pub is_dls: bool,
     pub url: String,
     pub sort: Sort,
+    ///The key in 'urls.txt' of the page this city was scraped from; e.g. 'Europa'.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_page: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -43,16 +57,169 @@ pub struct DayOfWeek(Weekday);
 #[derive(Debug, Clone)]
 pub struct UtcOffset(FixedOffset);
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub enum Sort {
+    #[default]
     ByName,
     ByOffset,
 }
 
+///How [TimeData::merge] settles two pages disagreeing on a city's UTC-offset or DST-flag.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictPolicy {
+    ///Keep the city from the most recently fetched page.
+    PreferNewest,
+    ///Keep the city from the page with this key; between other pages the newest wins.
+    PreferPage(String),
+    ///Do not merge but return all conflicts.
+    Report,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    pub city: String,
+    pub kept: CityData,
+    pub rejected: CityData,
+}
+
 impl TimeData {
     pub fn page_uri(&self) -> &Uri {
         &self.page_uri.0
     }
+
+    ///How long ago the page was downloaded.
+    pub fn age(&self) -> Duration {
+        Utc::now() - self.fetched_at
+    }
+
+    ///Marks all cities as coming from the page with this key.
+    pub fn with_source_page(mut self, key: &str) -> TimeData {
+        self.city_times = self
+            .city_times
+            .into_iter()
+            .map(|c| CityData {
+                source_page: Some(key.to_string()),
+                ..c
+            })
+            .collect();
+        self
+    }
+
+    ///Merges pages - e.g. those from [crate::cache::fetch_time_data] - into one set of cities;
+    /// each city records the key of the page it was taken from.
+    /// The merged page_uri is that of the sole page, or empty when several pages were merged; the merged timestamps
+    /// are the oldest of all pages so that the merged data never appears fresher than its stalest part.
+    pub fn merge<'a, K, I>(
+        pages: I,
+        policy: &ConflictPolicy,
+    ) -> Result<TimeData, Vec<MergeConflict>>
+    where
+        K: AsRef<str>,
+        I: IntoIterator<Item = (K, &'a TimeData)>,
+    {
+        let (merged, conflicts) = TimeData::merge_resolving(pages, policy);
+        if *policy == ConflictPolicy::Report && !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        Ok(merged)
+    }
+
+    ///Same as [TimeData::merge] but the conflicts the policy resolved are returned as well; with
+    /// [ConflictPolicy::Report] the first city found is kept.
+    pub fn merge_resolving<'a, K, I>(
+        pages: I,
+        policy: &ConflictPolicy,
+    ) -> (TimeData, Vec<MergeConflict>)
+    where
+        K: AsRef<str>,
+        I: IntoIterator<Item = (K, &'a TimeData)>,
+    {
+        //Sorting the pages by key keeps the outcome independent of e.g. HashMap-ordering...
+        let pages: BTreeMap<String, &TimeData> = pages
+            .into_iter()
+            .map(|(k, td)| (k.as_ref().to_string(), td))
+            .collect();
+
+        let mut merged = TimeData::default();
+        let mut cities = BTreeMap::<String, (CityData, DateTime<Utc>)>::new();
+        let mut conflicts = Vec::new();
+
+        for (n, (key, td)) in pages.iter().enumerate() {
+            if n == 0 {
+                merged.page_uri = td.page_uri.clone();
+                merged.last_updated = td.last_updated;
+                merged.fetched_at = td.fetched_at;
+                merged.page_reference_time = td.page_reference_time;
+            } else {
+                merged.page_uri = Default::default();
+                merged.last_updated = merged.last_updated.min(td.last_updated);
+                merged.fetched_at = merged.fetched_at.min(td.fetched_at);
+                merged.page_reference_time = merged.page_reference_time.min(td.page_reference_time);
+            }
+
+            for city in td.city_times.iter() {
+                let candidate = CityData {
+                    source_page: Some(key.to_string()),
+                    ..city.clone()
+                };
+                match cities.get(&city.name) {
+                    None => {
+                        cities.insert(city.name.to_string(), (candidate, td.fetched_at));
+                    }
+                    Some((kept, kept_fetched_at)) if kept.conflicts_with(&candidate) => {
+                        let take_candidate = match policy {
+                            ConflictPolicy::PreferPage(page)
+                                if kept.source_page.as_ref() == Some(page) =>
+                            {
+                                false
+                            }
+                            ConflictPolicy::PreferPage(page) if key == page => true,
+                            ConflictPolicy::PreferNewest | ConflictPolicy::PreferPage(_) => {
+                                td.fetched_at > *kept_fetched_at
+                            }
+                            ConflictPolicy::Report => false,
+                        };
+                        let (kept, rejected) = if take_candidate {
+                            (candidate.clone(), kept.clone())
+                        } else {
+                            (kept.clone(), candidate)
+                        };
+                        if take_candidate {
+                            cities.insert(city.name.to_string(), (kept.clone(), td.fetched_at));
+                        }
+                        conflicts.push(MergeConflict {
+                            city: city.name.to_string(),
+                            kept,
+                            rejected,
+                        });
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        merged.city_times = cities.into_iter().map(|(_, (c, _))| c).collect();
+        (merged, conflicts)
+    }
+}
+
+impl Default for TimeData {
+    fn default() -> Self {
+        TimeData {
+            page_uri: Default::default(),
+            node_data: Default::default(),
+            last_updated: unix_epoch(),
+            fetched_at: unix_epoch(),
+            page_reference_time: unix_epoch(),
+            stale: false,
+            city_times: Default::default(),
+        }
+    }
+}
+
+///The 'never'-timestamp: used for defaults and for legacy documents holding empty or malformed date-time strings.
+pub fn unix_epoch() -> DateTime<Utc> {
+    Utc.timestamp(0, 0)
 }
 
 impl UriWrapper {
@@ -73,7 +240,7 @@ impl Serialize for UriWrapper {
     where
         S: Serializer,
     {
-        Bson::String(self.0.to_string()).serialize(serializer)
+        serializer.serialize_str(&self.0.to_string())
     }
 }
 
@@ -87,34 +254,22 @@ impl<'de> Deserialize<'de> for UriWrapper {
         impl<'de> Visitor<'de> for UriWrapperVisitor {
             type Value = UriWrapper;
 
-            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+            fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
                 formatter.write_str("Need an URI")
             }
 
-            fn visit_string<E>(self, s: String) -> Result<Self::Value, E>
+            //visit_string and visit_borrowed_str both fall back on visit_str...
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
             where
                 E: Error,
             {
-                if s.len() > 0 {
-                    Ok(UriWrapper(
-                        Uri::from_str(&s).expect(format!("Not an URI: {}", &s).as_str()),
-                    ))
-                } else {
-                    Err(Error::invalid_value(Unexpected::Str(&s), &self))
+                match Uri::from_str(s) {
+                    Ok(uri) if !s.is_empty() => Ok(UriWrapper(uri)),
+                    _ => Err(Error::invalid_value(Unexpected::Str(s), &self)),
                 }
             }
         }
-        deserializer.deserialize_identifier(UriWrapperVisitor)
-    }
-
-    fn deserialize_in_place<D>(
-        _deserializer: D,
-        _place: &mut Self,
-    ) -> Result<(), <D as Deserializer<'de>>::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        unimplemented!()
+        deserializer.deserialize_str(UriWrapperVisitor)
     }
 }
 
@@ -123,7 +278,7 @@ impl Serialize for UtcOffset {
     where
         S: Serializer,
     {
-        Bson::String(self.0.to_string()).serialize(serializer)
+        serializer.serialize_str(&self.0.to_string())
     }
 }
 
@@ -137,47 +292,43 @@ impl<'de> Deserialize<'de> for UtcOffset {
         impl<'de> Visitor<'de> for UtcOffsetVisitor {
             type Value = UtcOffset;
 
-            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
-                formatter.write_str("Need an UtcOffset:")
+            fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+                formatter.write_str("Need an UtcOffset: e.g. +13:00 or -00:45")
             }
 
-            fn visit_string<E>(self, s: String) -> Result<Self::Value, E>
+            //visit_string and visit_borrowed_str both fall back on visit_str...
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
             where
                 E: Error,
             {
-                if s.len() > 0 {
-                    Ok(UtcOffset(FixedOffset::east(
-                        time::UtcOffset::parse(&s.replace(":", ""), "%z")
-                            .expect(format!("Not a valid UTC-Offset string: {}", &s).as_str())
-                            .as_seconds(),
-                    )))
-                } else {
-                    Err(Error::invalid_value(Unexpected::Str(&s), &self))
+                match time::UtcOffset::parse(s.replace(":", ""), "%z") {
+                    Ok(offset) if !s.is_empty() => {
+                        Ok(UtcOffset(FixedOffset::east(offset.as_seconds())))
+                    }
+                    _ => Err(Error::invalid_value(Unexpected::Str(s), &self)),
                 }
             }
         }
-        deserializer.deserialize_identifier(UtcOffsetVisitor)
-    }
-
-    fn deserialize_in_place<D>(
-        _deserializer: D,
-        _place: &mut Self,
-    ) -> Result<(), <D as Deserializer<'de>>::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        unimplemented!()
+        deserializer.deserialize_str(UtcOffsetVisitor)
     }
 }
 
-impl Default for Sort {
-    fn default() -> Self {
-        Self::ByName
+impl Display for MergeConflict {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(
+            fmt,
+            "Merge-conflict for {}: kept {} from {:?}; rejected {} from {:?}",
+            self.city,
+            self.kept.utc_offset.get(),
+            self.kept.source_page,
+            self.rejected.utc_offset.get(),
+            self.rejected.source_page
+        )
     }
 }
 
 impl Display for TimeData {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         write!(
             fmt,
             "Scanned Page: {}\nElement Data:{}\nCurrent UTC: {}\nCity Times:\n{}",
@@ -193,6 +344,23 @@ impl Display for TimeData {
     }
 }
 
+impl CityData {
+    ///The country as it appears in the city's details-url; e.g. 'new-zealand' for
+    /// https://www.timeanddate.com/worldclock/new-zealand/auckland
+    pub fn country(&self) -> Option<&str> {
+        let mut path = self.url.split("/worldclock/").nth(1)?.split('/');
+        match (path.next(), path.next()) {
+            (Some(country), Some(_city)) if !country.is_empty() => Some(country),
+            _ => None,
+        }
+    }
+
+    ///Two pages disagree on a city when they report a different UTC-offset or DST-flag.
+    pub fn conflicts_with(&self, other: &CityData) -> bool {
+        self.utc_offset.get() != other.utc_offset.get() || self.is_dls != other.is_dls
+    }
+}
+
 impl PartialEq for CityData {
     fn eq(&self, other: &CityData) -> bool {
         self.name == other.name
@@ -225,7 +393,7 @@ impl Ord for CityData {
 }
 
 impl Display for CityData {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         write!(
             fmt,
             "{}",
@@ -294,28 +462,46 @@ impl Default for UtcOffset {
     }
 }
 
-#[macro_export]
-macro_rules! merge {
-    ( $( $more_time_data:expr ),* ) => {
-        {
-            let mut merged = TimeData::default();
-            $(
-               merged.city_times = merged.city_times.union(&$more_time_data.unwrap().city_times).cloned().collect();
-            )*
-           vec!(merged)
-        }
-    };
-}
+///(De)Serializes a DateTime<Utc> as an RFC3339-string; the format the time-data has always been stored in.
+/// Deserializing is lenient: the empty string that used to be the default - or any other unparsable
+/// value - becomes the Unix-epoch so the data is deemed expired instead of panicking the app.
+pub mod rfc3339 {
+    use super::*;
+
+    pub fn serialize<S>(date_time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&date_time.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Rfc3339Visitor;
+
+        impl<'de> Visitor<'de> for Rfc3339Visitor {
+            type Value = DateTime<Utc>;
 
-#[macro_export]
-macro_rules! to_vec {
-    ( $( $more_time_data:expr ),* ) => {
-        {
-            let mut vec = Vec::new();
-            $(
-               vec.push($more_time_data.unwrap());
-            )*
-            vec
+            fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+                formatter.write_str("Need an RFC3339 date-time string")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(parse(s))
+            }
         }
-    };
+        deserializer.deserialize_str(Rfc3339Visitor)
+    }
+
+    ///Parses an RFC3339-string into UTC; anything unparsable yields the Unix-epoch.
+    pub fn parse(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s.trim())
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or_else(|_| unix_epoch())
+    }
 }
@@ -0,0 +1,198 @@
+use std::error;
+use std::fmt;
+
+use chrono::offset::LocalResult;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+use crate::parse_timeanddate_dot_com::{CityData, TimeData};
+
+///What is to be converted: either an absolute instant or a wall-clock time in the 'from'-city.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeInput {
+    Instant(DateTime<Utc>),
+    Local(NaiveDateTime),
+}
+
+///The outcome of a conversion: the same instant as seen in both cities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    pub from: DateTime<FixedOffset>,
+    pub to: DateTime<FixedOffset>,
+    ///The number of calendar days the 'to'-date lies ahead (positive) or behind (negative) the 'from'-date.
+    pub day_rollover: i64,
+    ///When the local input time occurs twice - the hour repeated when DST ends - 'from' and 'to' hold the
+    /// earlier occurrence and this field holds the later occurrence in the 'to'-city.
+    pub later_alternative: Option<DateTime<FixedOffset>>,
+    ///True when IANA-zones were used for both cities; otherwise the scraped UTC-offsets were used.
+    pub used_iana_zones: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    UnknownCity(String),
+    ///The local time falls in the gap when the clocks spring forward; it never happened in the 'from'-city.
+    NonexistentLocalTime {
+        city: String,
+        local: NaiveDateTime,
+    },
+}
+
+///Which time-zone a city lives in: the IANA-zone when one could be matched by name, else the scraped offset.
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Iana(Tz),
+    Fixed(FixedOffset),
+}
+
+///E.g. "What is 15:00 in Auckland for Berlin?"
+///```
+/// use chrono::NaiveDate;
+/// use rusttimeanddatedotcomparser::parse_timeanddate_dot_com::CityData;
+/// use rusttimeanddatedotcomparser::time_conversion::{convert, TimeInput};
+///
+/// let auckland = CityData { name: String::from("Auckland"), ..Default::default() };
+/// let berlin = CityData { name: String::from("Berlin"), ..Default::default() };
+/// let three_pm = NaiveDate::from_ymd(2020, 8, 21).and_hms(15, 0, 0);
+///
+/// let c = convert(TimeInput::Local(three_pm), &auckland, &berlin).unwrap();
+/// assert_eq!(c.to.to_string(), "2020-08-21 05:00:00 +02:00");
+/// assert_eq!(c.day_rollover, 0);
+///```
+pub fn convert(
+    time: TimeInput,
+    from_city: &CityData,
+    to_city: &CityData,
+) -> Result<Conversion, ConversionError> {
+    let from_zone = zone_of(from_city);
+    let to_zone = zone_of(to_city);
+
+    //Pin the input down to one - or in case of ambiguity two - instants...
+    let (instant, later_instant) = match time {
+        TimeInput::Instant(utc) => (utc, None),
+        TimeInput::Local(local) => match resolve_local(&from_zone, &local) {
+            LocalResult::Single(utc) => (utc, None),
+            LocalResult::Ambiguous(earlier, later) => (earlier, Some(later)),
+            LocalResult::None => {
+                return Err(ConversionError::NonexistentLocalTime {
+                    city: from_city.name.to_string(),
+                    local,
+                })
+            }
+        },
+    };
+
+    let from = at(&from_zone, &instant);
+    let to = at(&to_zone, &instant);
+
+    Ok(Conversion {
+        day_rollover: (to.naive_local().date() - from.naive_local().date()).num_days(),
+        later_alternative: later_instant.map(|later| at(&to_zone, &later)),
+        used_iana_zones: matches!((from_zone, to_zone), (Zone::Iana(_), Zone::Iana(_))),
+        from,
+        to,
+    })
+}
+
+///Same as [convert] but the cities are looked up by name - case-insensitive - in the (merged) time-data.
+pub fn convert_by_name(
+    time: TimeInput,
+    time_data: &TimeData,
+    from_city: &str,
+    to_city: &str,
+) -> Result<Conversion, ConversionError> {
+    convert(
+        time,
+        find_city(time_data, from_city)?,
+        find_city(time_data, to_city)?,
+    )
+}
+
+fn find_city<'a>(time_data: &'a TimeData, name: &str) -> Result<&'a CityData, ConversionError> {
+    time_data
+        .city_times
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| ConversionError::UnknownCity(name.to_string()))
+}
+
+///The IANA-zone for a city is found when its name matches the last part of a zone-name;
+/// e.g. 'New York' becomes 'America/New_York'. The UTC-entry maps to the 'UTC'-zone.
+pub fn iana_zone(city: &CityData) -> Option<Tz> {
+    let name = city.name.trim().replace(" ", "_");
+    if name.is_empty() {
+        return None;
+    }
+    TZ_VARIANTS
+        .iter()
+        .find(|tz| {
+            tz.name()
+                .rsplit('/')
+                .take(1)
+                .any(|city_part| city_part.eq_ignore_ascii_case(&name))
+        })
+        .cloned()
+}
+
+///True when the city's IANA-zone has another UTC-offset at some instant between 'from' and 'to'; e.g. because
+/// DST started or ended. Cities without an IANA-zone never change.
+pub fn offset_changed_between(city: &CityData, from: &DateTime<Utc>, to: &DateTime<Utc>) -> bool {
+    let tz = match iana_zone(city) {
+        Some(tz) => tz,
+        None => return false,
+    };
+    let offset_at =
+        |instant: &DateTime<Utc>| tz.offset_from_utc_datetime(&instant.naive_utc()).fix();
+
+    //Transitions are months apart: looking once a week does not miss a pair that cancels out...
+    let from_offset = offset_at(from);
+    let mut instant = *from;
+    while instant < *to {
+        instant = (instant + Duration::weeks(1)).min(*to);
+        if offset_at(&instant) != from_offset {
+            return true;
+        }
+    }
+    false
+}
+
+fn zone_of(city: &CityData) -> Zone {
+    match iana_zone(city) {
+        Some(tz) => Zone::Iana(tz),
+        None => Zone::Fixed(city.utc_offset.get()),
+    }
+}
+
+fn resolve_local(zone: &Zone, local: &NaiveDateTime) -> LocalResult<DateTime<Utc>> {
+    match zone {
+        Zone::Iana(tz) => tz.from_local_datetime(local).map(|d| d.with_timezone(&Utc)),
+        Zone::Fixed(offset) => offset
+            .from_local_datetime(local)
+            .map(|d| d.with_timezone(&Utc)),
+    }
+}
+
+fn at(zone: &Zone, instant: &DateTime<Utc>) -> DateTime<FixedOffset> {
+    match zone {
+        Zone::Iana(tz) => {
+            let local = instant.with_timezone(tz);
+            local.with_timezone(&local.offset().fix())
+        }
+        Zone::Fixed(offset) => instant.with_timezone(offset),
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::UnknownCity(name) => write!(f, "Unknown city: '{}'", name),
+            ConversionError::NonexistentLocalTime { city, local } => write!(
+                f,
+                "The local time {} does not exist in {}: the clocks were moved forward",
+                local, city
+            ),
+        }
+    }
+}
+
+impl error::Error for ConversionError {}
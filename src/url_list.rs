@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Duration;
+
+///One page to download the time-data from; e.g. 'Europa=https://... ttl=120 enabled=false'
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlEntry {
+    pub key: String,
+    pub url: String,
+    ///The group - set by a '[Some Group]'-line - the entry is listed under; if any.
+    pub group: Option<String>,
+    ///Overrides the global Time To Live [minutes].
+    pub ttl: Option<Duration>,
+    pub parser: ParserKind,
+    ///The language of the page, e.g. 'en'; if it deviates from the site's default.
+    pub locale: Option<String>,
+    pub enabled: bool,
+    ///Where the entry was found: file and line number.
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+///How a page is to be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ParserKind {
+    ///A timeanddate.com world clock page; e.g. https://www.timeanddate.com/worldclock/?continent=europe
+    #[default]
+    WorldClock,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlListError {
+    pub file: PathBuf,
+    ///Zero when the file itself could not be read.
+    pub line: usize,
+    pub message: String,
+}
+
+///Reads the URL list; it looks like this:
+///```text
+/// # Comments start with '#' or '//'
+/// include more-urls.txt
+///
+/// [Continents]
+/// Europa=https://www.timeanddate.com/worldclock/?continent=europe ttl=120
+/// Asia=https://www.timeanddate.com/worldclock/?continent=asia enabled=false
+/// Home=${HOME_TIME_URL} locale=nl parser=worldclock
+///```
+/// Options follow the url separated by whitespace; '${NAME}' is replaced by the environment-variable NAME,
+/// except in comments.
+/// An included file - relative to the including file - is read in place of the include-line.
+/// Anything that cannot be understood is an error stating the file and line number.
+pub fn read<P: AsRef<Path>>(file: P) -> Result<Vec<UrlEntry>, UrlListError> {
+    read_with(file.as_ref(), &|name| env::var(name).ok())
+}
+
+///Same as [read] but the variables are looked up with 'vars'.
+pub fn read_with(
+    file: &Path,
+    vars: &dyn Fn(&str) -> Option<String>,
+) -> Result<Vec<UrlEntry>, UrlListError> {
+    let mut entries = Vec::new();
+    read_into(file, vars, &mut vec![], &mut entries)?;
+
+    //A key must be unique, otherwise which url would win?...
+    let mut keys = HashSet::new();
+    for e in entries.iter() {
+        if !keys.insert(e.key.as_str()) {
+            return Err(UrlListError::at(
+                &e.file,
+                e.line,
+                format!("duplicate key '{}'", e.key),
+            ));
+        }
+    }
+    Ok(entries)
+}
+
+fn read_into(
+    file: &Path,
+    vars: &dyn Fn(&str) -> Option<String>,
+    including: &mut Vec<PathBuf>,
+    entries: &mut Vec<UrlEntry>,
+) -> Result<(), UrlListError> {
+    let content = fs::read_to_string(file).map_err(|e| {
+        UrlListError::at(
+            file,
+            0,
+            format!(
+                "{}; this file contains the URLS to download the time-data from",
+                e
+            ),
+        )
+    })?;
+    let canonical = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    if including.contains(&canonical) {
+        return Err(UrlListError::at(
+            file,
+            0,
+            String::from("it includes itself"),
+        ));
+    }
+    including.push(canonical);
+
+    let mut group = None;
+    for (i, raw) in content.lines().enumerate() {
+        let number = i + 1;
+        let error = |message: String| UrlListError::at(file, number, message);
+        //A commented out entry may well use a variable that is no longer set...
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("//") {
+            continue;
+        }
+        let line = substitute(trimmed, vars).map_err(error)?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(included) = line.strip_prefix("include ") {
+            let included = file
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(included.trim());
+            read_into(&included, vars, including, entries).map_err(|e| {
+                if e.line == 0 {
+                    error(format!(
+                        "cannot include '{}': {}",
+                        included.display(),
+                        e.message
+                    ))
+                } else {
+                    e
+                }
+            })?;
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            group = Some(line[1..line.len() - 1].trim().to_string());
+            continue;
+        }
+        entries.push(parse_entry(line, group.clone(), file, number).map_err(error)?);
+    }
+    including.pop();
+    Ok(())
+}
+
+fn parse_entry(
+    line: &str,
+    group: Option<String>,
+    file: &Path,
+    number: usize,
+) -> Result<UrlEntry, String> {
+    let (key, rest) = line
+        .split_once('=')
+        .ok_or_else(|| format!("malformed entry '{}': expected key=url", line))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("malformed entry '{}': the key is missing", line));
+    }
+
+    //Urls contain no whitespace: whatever follows the url are its options...
+    let mut parts = rest.split_whitespace();
+    let url = parts
+        .next()
+        .ok_or_else(|| format!("malformed entry '{}': the url is missing", line))?;
+
+    let mut entry = UrlEntry {
+        key: key.to_string(),
+        url: url.to_string(),
+        group,
+        ttl: None,
+        parser: ParserKind::WorldClock,
+        locale: None,
+        enabled: true,
+        file: file.to_path_buf(),
+        line: number,
+    };
+    for option in parts {
+        let (name, value) = option
+            .split_once('=')
+            .ok_or_else(|| format!("malformed option '{}': expected name=value", option))?;
+        match name {
+            "ttl" => {
+                let minutes = value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|m| *m > 0)
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid ttl '{}': expected a positive number of minutes",
+                            value
+                        )
+                    })?;
+                entry.ttl = Some(Duration::minutes(minutes));
+            }
+            "parser" => {
+                entry.parser = match value {
+                    "worldclock" => ParserKind::WorldClock,
+                    _ => return Err(format!("unknown parser '{}': expected worldclock", value)),
+                }
+            }
+            "locale" => entry.locale = Some(value.to_string()),
+            "enabled" => {
+                entry.enabled = value.parse().map_err(|_| {
+                    format!(
+                        "invalid value for enabled '{}': expected true or false",
+                        value
+                    )
+                })?
+            }
+            _ => {
+                return Err(format!(
+                    "unknown option '{}': expected ttl, parser, locale or enabled",
+                    name
+                ))
+            }
+        }
+    }
+    Ok(entry)
+}
+
+///Replaces every '${NAME}' by the value of variable NAME.
+fn substitute(line: &str, vars: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| String::from("unterminated '${' in line"))?;
+        let name = &rest[start + 2..start + end];
+        let value =
+            vars(name).ok_or_else(|| format!("environment-variable '{}' is not set", name))?;
+        result.push_str(&rest[..start]);
+        result.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+impl UrlListError {
+    fn at(file: &Path, line: usize, message: String) -> UrlListError {
+        UrlListError {
+            file: file.to_path_buf(),
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for UrlListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}: {}", self.file.display(), self.message),
+            line => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+        }
+    }
+}
+
+impl error::Error for UrlListError {}
@@ -1,5 +1,7 @@
 #![warn(rust_2018_idioms)]
 
+#[cfg(feature = "http-fetch")]
+use async_std::future::timeout;
 use html5ever::tendril::TendrilSink;
 use html5ever::tree_builder::TreeBuilderOpts;
 use html5ever::{parse_document, ParseOpts};
@@ -22,9 +24,22 @@ pub fn get_dom(page_content: String) -> RcDom {
         .unwrap()
 }
 
-pub async fn fetch_url_body(url: &String) -> Result<String> {
-    let mut res = surf::get(url).await?;
-    let body = res.body_string().await?;
+#[cfg(feature = "http-fetch")]
+pub async fn fetch_url_body(
+    url: &str,
+    limit: std::time::Duration,
+    locale: Option<&str>,
+) -> Result<String> {
+    //A page that takes too long is treated like any page that could not be downloaded...
+    let body = timeout(limit, async {
+        let mut request = surf::get(url);
+        if let Some(locale) = locale {
+            request = request.set_header("Accept-Language", locale);
+        }
+        let mut res = request.await?;
+        res.body_string().await
+    })
+    .await??;
     //    println!("\n**********************START PAGE BODY Body*******************\n\n{}\n\n**********************END PAGE BODY**********************\n", body);
     Ok(body)
 }
@@ -1,6 +1,8 @@
 use rusttimeanddatedotcomparser as r;
+use rusttimeanddatedotcomparser::config::Config;
 use rusttimeanddatedotcomparser::parse_timeanddate_dot_com::TimeData;
 use std::collections::HashMap;
+use std::env;
 
 #[test]
 fn main_test() {
@@ -16,12 +18,19 @@ fn main_test() {
 //}
 #[test]
 fn fetch_time_data_test() {
+    //As the program does, without the test-harness' arguments...
+    let env_vars: Vec<(String, String)> = env::vars().collect();
+    let config = Config::load(&[], &env_vars).unwrap();
+
     //clear the cache...
-    r::mongo_api::replace_stored_data_with(&HashMap::<String, TimeData>::new());
+    r::mongo_api::replace_stored_data_with(
+        &config.cache.mongo,
+        &HashMap::<String, TimeData>::new(),
+    );
 
-    //Fresh time-data will now be downloaded and cached (when 'cache' parameter has been provided)...
-    assert_eq!(r::cache::fetch_time_data().len(), 7);
+    //Fresh time-data will now be downloaded and cached (when the cache has been enabled)...
+    assert_eq!(r::cache::fetch_time_data(&config).unwrap().len(), 7);
 
-    //Fresh time-data will now loaded from cache (when 'cache' parameter has been provided)...
-    assert_eq!(r::cache::fetch_time_data().len(), 7);
+    //Fresh time-data will now loaded from cache (when the cache has been enabled)...
+    assert_eq!(r::cache::fetch_time_data(&config).unwrap().len(), 7);
 }
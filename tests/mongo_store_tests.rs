@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration as StdDuration;
+
+use async_std::task;
+use mongodb::bson::doc;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::Client;
+use regex::Regex;
+
+use chrono::{Duration, FixedOffset, Utc};
+use rusttimeanddatedotcomparser::cache_store::CacheStore;
+use rusttimeanddatedotcomparser::city_query::CityQuery;
+use rusttimeanddatedotcomparser::history::{HistoryStore, Retention};
+use rusttimeanddatedotcomparser::mongo_api::{MongoStore, DEFAULT_URI, SCHEMA_VERSION};
+use rusttimeanddatedotcomparser::parse_timeanddate_dot_com::{CityData, TimeData, UtcOffset};
+
+///These tests need a running mongod: a local one, or the one set with TIMEANDDATE_TEST_MONGO_URI.
+fn uri() -> String {
+    env::var("TIMEANDDATE_TEST_MONGO_URI").unwrap_or_else(|_| DEFAULT_URI.to_string())
+}
+
+fn store(collection: &str) -> MongoStore {
+    MongoStore::connect(&uri(), "test", collection).expect("Could not connect to the test-mongod")
+}
+
+fn page(city: &str) -> TimeData {
+    let mut td = TimeData::default();
+    td.city_times.insert(CityData {
+        name: city.to_string(),
+        ..Default::default()
+    });
+    td
+}
+
+#[test]
+fn mongo_store_test() {
+    let store = store("mongo_store_test");
+    store.replace_all(&HashMap::new()).unwrap();
+    assert!(store.load_all().unwrap().is_empty());
+
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    pages.insert(String::from("Asia"), page("Tokyo"));
+    store.replace_all(&pages).unwrap();
+    assert_eq!(store.load_all().unwrap().len(), 2);
+
+    //All pages are written in one round-trip...
+    let inserts = store.metrics().operations["insert_many"];
+    assert_eq!((inserts.count, inserts.documents), (1, 2));
+
+    //The clone shares the client with the original...
+    let shared = store.clone();
+    shared.put("Afrika", &page("Cairo")).unwrap();
+    assert_eq!(
+        store
+            .get("Afrika")
+            .unwrap()
+            .unwrap()
+            .city_times
+            .iter()
+            .next()
+            .unwrap()
+            .name,
+        "Cairo"
+    );
+
+    //Any number of pages is put in the same round-trips: one for the cities, one for the pages...
+    let before = store.metrics().operations["insert_many"].count;
+    let mut more = HashMap::new();
+    more.insert(String::from("Asia"), page("Seoul"));
+    more.insert(String::from("Oceania"), page("Auckland"));
+    store.put_many(&more).unwrap();
+    assert_eq!(store.metrics().operations["insert_many"].count - before, 2);
+    assert_eq!(store.get("Asia").unwrap().unwrap().city_times.len(), 1);
+
+    //Removing a page switches generations as well: readers never see a page without its cities...
+    let before = store.metrics().operations["activate"].count;
+    store.remove("Asia").unwrap();
+    assert_eq!(store.metrics().operations["activate"].count - before, 1);
+    assert!(store.get("Asia").unwrap().is_none());
+    assert_eq!(store.load_all().unwrap().len(), 3);
+}
+
+#[test]
+fn half_written_generation_is_never_read_test() {
+    let store = store("mongo_generation_test");
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    store.replace_all(&pages).unwrap();
+
+    //A replace that crashed after writing one page of the generation it was handed...
+    let database = task::block_on(Client::with_uri_str(&uri()))
+        .unwrap()
+        .database("test");
+    let generations = database.collection("mongo_generation_test_generations");
+    let crashed = task::block_on(
+        generations.find_one_and_update(
+            doc! {"_id": "counter"},
+            doc! {"$inc": {"generation": 1_i64}},
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        ),
+    )
+    .unwrap()
+    .unwrap()
+    .get_i64("generation")
+    .unwrap();
+    let pages_collection = database.collection("mongo_generation_test");
+    task::block_on(pages_collection.insert_one(
+        doc! {"_id": format!("{}/Asia", crashed), "key": "Asia", "generation": crashed},
+        None,
+    ))
+    .unwrap();
+
+    //...is not seen by readers, nor by the next replace...
+    assert_eq!(
+        store.load_all().unwrap().keys().collect::<Vec<_>>(),
+        vec!["Europa"]
+    );
+    pages.insert(String::from("Afrika"), page("Cairo"));
+    store.replace_all(&pages).unwrap();
+    let mut keys: Vec<String> = store.load_all().unwrap().into_keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["Afrika", "Europa"]);
+
+    //...and it is cleared once a later generation has been replaced as well.
+    store.replace_all(&pages).unwrap();
+    let leftovers =
+        task::block_on(pages_collection.count_documents(doc! {"generation": crashed}, None))
+            .unwrap();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn concurrent_replaces_test() {
+    let pages = |writer: &str| -> HashMap<String, TimeData> {
+        (0..20)
+            .map(|i| (format!("Page{}", i), page(&format!("{} {}", writer, i))))
+            .collect()
+    };
+    for _ in 0..5 {
+        //Two machines replacing the pages of one cache at the same time...
+        let writers: Vec<_> = ["First", "Second"]
+            .iter()
+            .map(|writer| {
+                let store = store("mongo_concurrent_test");
+                let pages = pages(writer);
+                std::thread::spawn(move || store.restore_all(&pages).unwrap())
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        //...leave all the pages of one of them: nothing is missing and nothing is mixed.
+        let loaded = store("mongo_concurrent_test").load_all().unwrap();
+        assert_eq!(loaded.len(), 20);
+        let winner = loaded["Page0"]
+            .city_times
+            .iter()
+            .next()
+            .unwrap()
+            .name
+            .clone();
+        let winner = winner.split(' ').next().unwrap().to_string();
+        for (key, td) in loaded.iter() {
+            let cities: Vec<&String> = td.city_times.iter().map(|c| &c.name).collect();
+            assert_eq!(
+                cities,
+                vec![&format!("{} {}", winner, &key["Page".len()..])]
+            );
+        }
+    }
+}
+
+#[test]
+fn server_side_query_test() {
+    let city = |name: &str, url: &str, offset: i32, is_dls: bool| CityData {
+        name: name.to_string(),
+        url: url.to_string(),
+        utc_offset: UtcOffset::from(FixedOffset::east(offset)),
+        is_dls,
+        ..Default::default()
+    };
+    let mut asia = TimeData::default();
+    asia.city_times
+        .insert(city("Kolkata", "/worldclock/india/kolkata", 19800, false));
+    asia.city_times.insert(city(
+        "Colombo",
+        "/worldclock/sri-lanka/colombo",
+        19800,
+        false,
+    ));
+    asia.city_times
+        .insert(city("Tokyo", "/worldclock/japan/tokyo", 32400, false));
+    let mut europa = TimeData::default();
+    europa
+        .city_times
+        .insert(city("Berlin", "/worldclock/germany/berlin", 7200, true));
+
+    let store = store("mongo_query_test");
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Asia"), asia);
+    pages.insert(String::from("Europa"), europa);
+    store.replace_all(&pages).unwrap();
+
+    let names = |query: CityQuery| {
+        store
+            .query(&query)
+            .unwrap()
+            .iter()
+            .map(|c| c.name.to_string())
+            .collect::<Vec<_>>()
+    };
+    let india = FixedOffset::east(19800);
+    assert_eq!(
+        names(CityQuery::new().offset_between(india, india)),
+        vec!["Colombo", "Kolkata"]
+    );
+    assert_eq!(
+        names(CityQuery::new().country("Sri Lanka")),
+        vec!["Colombo"]
+    );
+    assert_eq!(names(CityQuery::new().dst(true)), vec!["Berlin"]);
+    assert_eq!(
+        names(CityQuery::new().continent("asia").name_contains("o")),
+        vec!["Colombo", "Kolkata", "Tokyo"]
+    );
+    //What the name should contain is no regex...
+    assert_eq!(
+        names(CityQuery::new().name_contains("KOL")),
+        vec!["Kolkata"]
+    );
+    assert!(names(CityQuery::new().name_contains(".")).is_empty());
+    //...and a Rust-regex - with a class-intersection PCRE does not know of - is matched as a Rust-regex.
+    assert_eq!(
+        names(CityQuery::new().name_matches(Regex::new("^[A-Z&&[^K]]o").unwrap())),
+        vec!["Colombo", "Tokyo"]
+    );
+
+    //The cities live in their own documents but the pages are whole again when loaded...
+    assert_eq!(store.get("Asia").unwrap().unwrap().city_times.len(), 3);
+    assert_eq!(store.load_all().unwrap()["Europa"].city_times.len(), 1);
+}
+
+#[test]
+fn mongo_history_test() {
+    let store = store("mongo_history_test");
+    task::block_on(
+        task::block_on(Client::with_uri_str(&uri()))
+            .unwrap()
+            .database("test")
+            .collection("mongo_history_test_history")
+            .drop(None),
+    )
+    .unwrap();
+
+    let now = Utc::now();
+    let mut pages = HashMap::new();
+    for days_ago in (0..5).rev() {
+        pages.insert(String::from("Europa"), page("Berlin"));
+        store
+            .append(now - Duration::days(days_ago), &pages)
+            .unwrap();
+    }
+    assert_eq!(store.timeline("BERLIN").unwrap().len(), 5);
+    //The index is created by the first append only...
+    assert_eq!(store.metrics().operations["create_history_index"].count, 1);
+
+    let retention = Retention {
+        max_age: Some(Duration::days(3) + Duration::minutes(1)),
+        max_snapshots: Some(2),
+    };
+    assert_eq!(store.prune(&retention).unwrap(), 3);
+    let timeline = store.timeline("Berlin").unwrap();
+    assert_eq!(timeline.len(), 2);
+    assert_eq!(
+        timeline.last().unwrap().taken_at.timestamp_millis(),
+        now.timestamp_millis()
+    );
+}
+
+#[test]
+fn schema_migration_test() {
+    let store = store("mongo_migration_test");
+    let database = task::block_on(Client::with_uri_str(&uri()))
+        .unwrap()
+        .database("test");
+    for name in [
+        "mongo_migration_test",
+        "mongo_migration_test_generations",
+        "mongo_migration_test_cities",
+    ]
+    .iter()
+    {
+        task::block_on(database.collection(name).drop(None)).unwrap();
+    }
+
+    //A page stored before there were generations and schema-versions, next to one that no version can read...
+    let collection = database.collection("mongo_migration_test");
+    task::block_on(collection.insert_many(
+        vec![
+            doc! {
+                "_id": "Europa",
+                "page_uri": "https://www.timeanddate.com/worldclock/?continent=europe",
+                "node_data": "",
+                "city_times": [],
+                "last_updated": "2020-08-20T10:00:00+00:00",
+            },
+            doc! {"_id": "Garbage", "page_uri": 42},
+        ],
+        None,
+    ))
+    .unwrap();
+
+    //The same goes for the cities...
+    let cities = database.collection("mongo_migration_test_cities");
+    task::block_on(cities.insert_many(
+        vec![
+            doc! {
+                "generation": 1_i64,
+                "page_key": "Asia",
+                "id": 1,
+                "name": "Tokyo",
+                "time_string": "",
+                "utc_offset": "+09:00",
+                "is_dls": false,
+                "url": "https://www.timeanddate.com/worldclock/japan/tokyo",
+                "sort": "ByName",
+            },
+            doc! {"generation": 1_i64, "page_key": "Asia", "name": 42},
+        ],
+        None,
+    ))
+    .unwrap();
+
+    //Loading skips the unreadable document instead of failing...
+    assert_eq!(
+        store.load_all().unwrap().keys().collect::<Vec<_>>(),
+        vec!["Europa"]
+    );
+
+    let report = store.migrate_stored_data().unwrap();
+    assert_eq!(report.migrated, 2);
+    assert_eq!(report.unreadable.len(), 1);
+    assert_eq!(report.unreadable_cities.len(), 1);
+    let tokyo = task::block_on(cities.find_one(doc! {"name": "Tokyo"}, None))
+        .unwrap()
+        .unwrap();
+    assert_eq!(tokyo.get_i32("utc_offset_seconds").unwrap(), 9 * 3600);
+    let migrated = task::block_on(collection.find_one(doc! {"_id": "Europa"}, None))
+        .unwrap()
+        .unwrap();
+    assert_eq!(migrated.get_i32("schema_version").unwrap(), SCHEMA_VERSION);
+    assert_eq!(store.migrate_stored_data().unwrap().migrated, 0);
+}
+
+#[test]
+fn cache_watcher_test() {
+    let watched = store("mongo_watcher_test");
+    let mut pages = HashMap::new();
+    pages.insert(String::from("Europa"), page("Berlin"));
+    watched.replace_all(&pages).unwrap();
+
+    //With change streams or - on a standalone mongod - by polling...
+    let watcher = watched.watch(StdDuration::from_secs(1));
+    let updates = watcher.subscribe();
+    let first = updates.recv_timeout(StdDuration::from_secs(30)).unwrap();
+    assert_eq!(first.keys().collect::<Vec<_>>(), vec!["Europa"]);
+
+    //Another machine sharing the cache refreshes it...
+    let other = store("mongo_watcher_test");
+    pages.insert(String::from("Asia"), page("Tokyo"));
+    other.replace_all(&pages).unwrap();
+    let changed = updates.recv_timeout(StdDuration::from_secs(30)).unwrap();
+    assert_eq!(changed.len(), 2);
+
+    watcher.stop();
+    assert!(updates.recv().is_err());
+}